@@ -0,0 +1,53 @@
+// Time taken to build the contact networks and to run the first days of the
+// epidemic for large populations, with and without `event_driven`. Run with
+// `cargo bench --bench graph`. The populations can be given as arguments,
+// e.g. `cargo bench --bench graph -- 300000`.
+
+use std::env;
+use std::time::Instant;
+
+use graph::{Config, Simulation};
+
+const POPULATIONS: [usize; 2] = [300_000, 3_000_000];
+const DAYS: usize = 30;
+
+fn config(total_population: usize, event_driven: bool) -> Config {
+    let mut config = serde_json::to_value(Config::default()).unwrap();
+    config["total_population"] = total_population.into();
+    config["event_driven"] = event_driven.into();
+    config["initial_outbreak_size"] = (total_population / 1000).into();
+    config["seed"] = 1.into();
+    serde_json::from_value(config).unwrap()
+}
+
+fn main() {
+    // Cargo passes `--bench` to the benchmark binaries.
+    let mut populations: Vec<usize> = env::args()
+        .skip(1)
+        .filter_map(|arg| arg.parse().ok())
+        .collect();
+    if populations.is_empty() {
+        populations = POPULATIONS.to_vec();
+    }
+    for n in populations {
+        for event_driven in [false, true].iter() {
+            let config = config(n, *event_driven);
+            let start = Instant::now();
+            let mut simulation = Simulation::try_new(config).unwrap();
+            let build = start.elapsed();
+
+            let start = Instant::now();
+            for _ in 0..DAYS {
+                simulation.tick();
+            }
+            let tick = start.elapsed() / DAYS as u32;
+            println!(
+                "{:>9} agents{}: build {:>8.3} s, tick {:>8.3} ms",
+                n,
+                if *event_driven { ", event driven" } else { "" },
+                build.as_secs_f64(),
+                tick.as_secs_f64() * 1e3
+            );
+        }
+    }
+}
@@ -23,6 +23,11 @@ async function init_forms(){
 			await vegaEmbed(vis_div, timeprofile_spec(title, xlabel, ylabel), opts)
 		).view;
 		input.addEventListener("input", (event) =>{
+			if (input.getAttribute("data-optional") === "true" && event.target.value === ""){
+				event.target.setCustomValidity("");
+				view.data("mydata", []).run();
+				return;
+			}
 			let data = listToProbs(event.target.value);
 			if (data===null || data.length === 0){
 				event.target.setCustomValidity("Must be a list of probabilities");
@@ -36,7 +41,7 @@ async function init_forms(){
 		let multiply_input = form.querySelector(".multiply-inp");
 		let multiply_btn = form.querySelector(".multiply-btn");
 		input.addEventListener("input", (event) => {
-			multiply_btn.disabled = !event.target.checkValidity();
+			multiply_btn.disabled = !event.target.checkValidity() || event.target.value === "";
 		});
 		multiply_btn.addEventListener("click", (event)=>{
 			let value = listToPosNums(input.value);
@@ -84,6 +89,25 @@ export function getConfig(){
 	return parseForm(configureForm);
 }
 
+// Mark the fields rejected by the simulation as invalid and return a summary
+// of the problems.
+export function showConfigErrors(errors){
+	let messages = [];
+	for (let err of errors){
+		messages.push(err.message);
+		if (err.field === null){
+			continue;
+		}
+		let ele = configureForm.querySelector(`[name=${err.field}]`);
+		if (ele === null){
+			continue;
+		}
+		ele.setCustomValidity(err.message);
+		ele.addEventListener("input", () => ele.setCustomValidity(""), {once: true});
+	}
+	return messages.join("\n");
+}
+
 configureClose.addEventListener("click", (event) => {
 	if(!configureForm.checkValidity()){
 		event.preventDefault();
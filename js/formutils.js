@@ -9,6 +9,8 @@ export function parseForm(form){
         let value = fd.get(key);
         if (ele.getAttribute("type") === "checkbox"){
             value = ele.checked;
+        }else if (ele.getAttribute("data-optional") === "true" && value === ""){
+            value = ele.getAttribute("data-type") === "list" ? [] : null;
         }else if (ele.getAttribute("data-type") === "list"){
             if(ele.getAttribute("data-units") === "percent"){
                 value = value.split(",").map((x) => {return Number(x)/100});
@@ -32,6 +34,9 @@ export function parseForm(form){
 export function fillForm(form, data){
     for (let [key, value] of Object.entries(data)){
         let ele = form.querySelector(`[name=${key}]`);
+        if (ele === null){
+            continue;
+        }
         if(ele.getAttribute("data-units")==="percent"){
             if(ele.getAttribute("data-type")==="list"){
                 value = value.map((x) => {return Number((x*100).toPrecision(5))}).join(", ");
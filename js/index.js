@@ -7,7 +7,7 @@ import vegaEmbed from 'vega-embed';
 
 
 import {population_spec, severe_spec, daily_events_spec, r_spec, serial_spec} from "./plot_specs.js"
-import {fillConfigForm, getConfig} from './forms.js';
+import {fillConfigForm, getConfig, showConfigErrors} from './forms.js';
 import {fillPolicyForm, getPolicies} from './policy_forms.js'
 
 
@@ -219,6 +219,8 @@ function simplify_counter_data(data){
     data["Immune"] = data["Immune (Undetected)"] + data["Immune (Detected)"]
     delete data["Immune (Detected)"];
     delete data["Immune (Undetected)"];
+    data["Vaccinated"] += data["Partially vaccinated"];
+    delete data["Partially vaccinated"];
 }
 
 
@@ -252,7 +254,8 @@ function handleIncomingData(data){
 	}
 	if (
 		!stopped_once &&
-		counter_output["Infected (Undetected)"] + counter_output["Infected (Detected)"] === 0
+		counter_output["Exposed"] + counter_output["Infected (Undetected)"] +
+			counter_output["Asymptomatic"] + counter_output["Infected (Detected)"] === 0
 	){
 		pause();
 		endStyles();
@@ -310,6 +313,12 @@ worker.onmessage = function(e){
 		case "POLICY_REVERSED":
 			handlePolicyData(msg.args);
 			break;
+		case "CONFIG_ERROR":
+			display.innerHTML = "Invalid configuration:\n" + showConfigErrors(msg.args);
+			reset();
+			playPauseButton.textContent = "▶";
+			playPauseButton.disabled = false;
+			break;
 	}
 }
 
@@ -53,8 +53,8 @@ export const timeprofile_spec =  function(title, xlabel, ylabel){
     };
 }
 
-const categories = ["Severe", "Dead", "Infected (Undetected)", "Infected (Detected)", "Immune", "Susceptible"];
-const colors = ["#e7298a", "#666666", "#d95f02", "#e6ab02", "#66a61e", "#1b9e77"];
+const categories = ["Severe", "Dead", "Infected (Undetected)", "Infected (Detected)", "Asymptomatic", "Exposed", "Immune", "Vaccinated", "Susceptible"];
+const colors = ["#e7298a", "#666666", "#d95f02", "#e6ab02", "#a6761d", "#7570b3", "#66a61e", "#1f78b4", "#1b9e77"];
 
 
 export const dist_spec = {
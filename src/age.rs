@@ -0,0 +1,167 @@
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::validate::{Reason, Validator};
+use crate::{Config, Counter};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AgeBand {
+    pub name: String,
+    // Relative weight of the band in the population.
+    pub weight: f64,
+    // Whether agents in this band can live without an adult in the household.
+    pub adult: bool,
+    // Whether agents in this band go to school instead of to a workplace.
+    #[serde(default)]
+    pub school: bool,
+}
+
+impl AgeBand {
+    fn new(name: &str, weight: f64, adult: bool, school: bool) -> AgeBand {
+        AgeBand {
+            name: name.to_string(),
+            weight,
+            adult,
+            school,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AgeCounter {
+    pub age_band: String,
+    #[serde(flatten)]
+    pub counter: Counter,
+}
+
+//https://www.ine.es/jaxiT3/Datos.htm?t=9663
+pub(crate) fn default_age_bands() -> Vec<AgeBand> {
+    vec![
+        AgeBand::new("0-19", 19.5, false, true),
+        AgeBand::new("20-39", 24.5, true, false),
+        AgeBand::new("40-59", 31.5, true, false),
+        AgeBand::new("60-79", 18.5, true, false),
+        AgeBand::new("80+", 6.0, true, false),
+    ]
+}
+
+// Samples the age band of the members of a household. The first member is
+// always an adult. The rest are sampled so that the whole population follows
+// the age pyramid as closely as possible.
+pub(crate) struct AgeSampler {
+    all: WeightedIndex<f64>,
+    adults: Vec<usize>,
+    adult_sampler: WeightedIndex<f64>,
+}
+
+impl AgeSampler {
+    pub(crate) fn new(bands: &[AgeBand], family_sizes: &[usize], family_weights: &[f64]) -> Self {
+        let total: f64 = bands.iter().map(|b| b.weight).sum();
+        let adults: Vec<usize> = (0..bands.len()).filter(|i| bands[*i].adult).collect();
+        let adult_total: f64 = adults.iter().map(|i| bands[*i].weight).sum();
+        let adult_sampler = WeightedIndex::new(adults.iter().map(|i| bands[*i].weight)).unwrap();
+
+        // Fraction of the agents that head a household.
+        let mean_size = family_sizes
+            .iter()
+            .zip(family_weights)
+            .map(|(s, w)| *s as f64 * w)
+            .sum::<f64>()
+            / family_weights.iter().sum::<f64>();
+        let heads = 1. / mean_size;
+        // Remove the heads from the pyramid. If there are not enough adults
+        // for all the households, the rest of the members are all minors.
+        let rest: Vec<f64> = bands
+            .iter()
+            .map(|b| {
+                let head = if b.adult { b.weight / adult_total } else { 0. };
+                f64::max(b.weight / total - heads * head, 0.)
+            })
+            .collect();
+        let all = if rest.iter().sum::<f64>() > 0. {
+            WeightedIndex::new(rest).unwrap()
+        } else {
+            WeightedIndex::new(bands.iter().map(|b| b.weight)).unwrap()
+        };
+        AgeSampler {
+            all,
+            adults,
+            adult_sampler,
+        }
+    }
+
+    pub(crate) fn sample<R: Rng>(&self, rng: &mut R, id_in_family: usize) -> usize {
+        if id_in_family == 0 {
+            self.adults[self.adult_sampler.sample(rng)]
+        } else {
+            self.all.sample(rng)
+        }
+    }
+}
+
+impl Config {
+    // The profile of an age band, or the one of the whole population when
+    // there are no per age band profiles.
+    pub(crate) fn infected_severe_profile_for(&self, band: usize) -> &[f64] {
+        self.infected_severe_profile_by_age
+            .get(band)
+            .unwrap_or(&self.infected_severe_profile)
+    }
+
+    // Same for the severe to dead profile.
+    pub(crate) fn severe_dead_profile_for(&self, band: usize) -> &[f64] {
+        self.severe_dead_profile_by_age
+            .get(band)
+            .unwrap_or(&self.severe_dead_profile)
+    }
+
+    // Agents are fully susceptible when there are no per age band values.
+    pub(crate) fn susceptibility_for(&self, band: usize) -> f64 {
+        self.susceptibility_by_age.get(band).copied().unwrap_or(1.)
+    }
+
+    pub(crate) fn validate_ages(&self, v: &mut Validator) {
+        let nerrors = v.len();
+        if self.age_bands.is_empty() {
+            v.push("age_bands", Reason::Empty);
+        }
+        for band in self.age_bands.iter() {
+            v.non_negative("age_bands", band.weight);
+        }
+        if v.len() == nerrors {
+            if self.age_bands.iter().map(|b| b.weight).sum::<f64>() <= 0. {
+                v.push("age_bands", Reason::ZeroSum);
+            } else if !self.age_bands.iter().any(|b| b.adult && b.weight > 0.) {
+                v.push("age_bands", Reason::NoAdults);
+            }
+        }
+
+        let nbands = self.age_bands.len();
+        for (field, profiles) in [
+            (
+                "infected_severe_profile_by_age",
+                &self.infected_severe_profile_by_age,
+            ),
+            ("severe_dead_profile_by_age", &self.severe_dead_profile_by_age),
+        ]
+        .iter()
+        {
+            if !profiles.is_empty() && profiles.len() != nbands {
+                v.push(field, Reason::LengthMismatch { other: "age_bands" });
+            }
+            for profile in profiles.iter() {
+                v.profile(field, profile);
+            }
+        }
+        if !self.susceptibility_by_age.is_empty() && self.susceptibility_by_age.len() != nbands {
+            v.push(
+                "susceptibility_by_age",
+                Reason::LengthMismatch { other: "age_bands" },
+            );
+        }
+        for s in self.susceptibility_by_age.iter() {
+            v.non_negative("susceptibility_by_age", *s);
+        }
+    }
+}
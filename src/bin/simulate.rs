@@ -0,0 +1,489 @@
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process;
+
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+
+use graph::{
+    run_ensemble, run_sweep, Band, BranchReport, BranchSpec, Config, ConfigError, Counter,
+    EnsembleSpec, EnsembleSummary, HistoryEntry, ImportedNetworks, Metric, Policy, Simulation,
+    SweepRow, SweepSpec, Table, TransmissionTree, DEFAULT_QUANTILES,
+};
+
+const USAGE: &str = "\
+Run the simulation without a browser and write the daily results to stdout.
+
+Usage: simulate [options]
+
+Options:
+    --config FILE     Simulation configuration (JSON or TOML). Missing fields
+                      take the default values.
+    --policies FILE   Policy schedule (JSON or TOML).
+    --days N          Maximum number of days to simulate [default: 365, or
+                      the value in the sweep or branch specification].
+    --format FORMAT   Output format, csv or json [default: csv].
+    --replicas K      Run K replicas with different seeds and output the
+                      mean, median and quantiles of each quantity, for every
+                      day. All replicas run for the full number of days.
+    --quantiles LIST  Comma separated quantiles to output when running
+                      replicas [default: 0.05,0.25,0.75,0.95].
+    --sweep FILE      Run the variants of the configuration given by the
+                      sweep specification (JSON or TOML) and output one row
+                      per run with the parameters and the outcome.
+    --branches FILE   Run until the fork day of the branch specification
+                      (JSON or TOML), then run a branch per scenario, each
+                      with its own policies on top of the common ones, and
+                      output the deaths, peak of Severe agents and attack
+                      rate of each branch and their difference with the
+                      first one.
+    --output FILE     Write the daily results of a single run to FILE instead
+                      of stdout, as CSV, JSON, Arrow IPC or Parquet depending
+                      on the extension (.csv, .json, .arrow/.feather or
+                      .parquet). Arrow and Parquet need the columnar feature.
+    --agents FILE     Write the final state of every agent of a single run to
+                      FILE (.csv, .arrow/.feather or .parquet).
+    --transmissions FILE
+                      Record every infection of a single run and write the
+                      transmission tree to FILE, as a CSV edge list, a
+                      Newick forest or nested JSON depending on the
+                      extension (.csv, .nwk or .json).
+    --networks DIR    Write the contact layers at the end of a single run to
+                      DIR: the agents with their household, groups, age band
+                      and state to nodes.csv, the links of each layer to
+                      LAYER.csv, all of them to contacts.graphml and the
+                      statistics of each layer to stats.json.
+    --nodes FILE      Read the agents from FILE instead of generating them.
+                      FILE has a header with the columns id, household and
+                      age, and optionally the group of each agent in other
+                      layers (e.g. workplace), separated by commas or tabs.
+    --edges LAYER=FILE
+                      Read the contacts of LAYER (e.g. family, workplace or
+                      world) from FILE, with the columns source, target and
+                      optionally weight. Needs --nodes. Can be repeated.
+    -h, --help        Print this message.
+";
+
+enum Format {
+    Csv,
+    Json,
+}
+
+struct Args {
+    config: Option<String>,
+    policies: Option<String>,
+    days: Option<usize>,
+    format: Format,
+    replicas: Option<usize>,
+    quantiles: Vec<f64>,
+    sweep: Option<String>,
+    branches: Option<String>,
+    output: Option<String>,
+    agents: Option<String>,
+    transmissions: Option<String>,
+    networks: Option<String>,
+    nodes: Option<String>,
+    edges: Vec<(String, String)>,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = Args {
+        config: None,
+        policies: None,
+        days: None,
+        format: Format::Csv,
+        replicas: None,
+        quantiles: DEFAULT_QUANTILES.to_vec(),
+        sweep: None,
+        branches: None,
+        output: None,
+        agents: None,
+        transmissions: None,
+        networks: None,
+        nodes: None,
+        edges: Vec::new(),
+    };
+    let mut it = env::args().skip(1);
+    while let Some(arg) = it.next() {
+        let mut value = || it.next().ok_or(format!("Missing value for {}", arg));
+        match arg.as_str() {
+            "--config" => args.config = Some(value()?),
+            "--policies" => args.policies = Some(value()?),
+            "--days" => {
+                args.days = Some(
+                    value()?
+                        .parse()
+                        .map_err(|e| format!("Invalid number of days: {}", e))?,
+                )
+            }
+            "--format" => {
+                args.format = match value()?.as_str() {
+                    "csv" => Format::Csv,
+                    "json" => Format::Json,
+                    other => return Err(format!("Unknown format {}", other)),
+                }
+            }
+            "--sweep" => args.sweep = Some(value()?),
+            "--branches" => args.branches = Some(value()?),
+            "--output" => args.output = Some(value()?),
+            "--agents" => args.agents = Some(value()?),
+            "--transmissions" => args.transmissions = Some(value()?),
+            "--networks" => args.networks = Some(value()?),
+            "--nodes" => args.nodes = Some(value()?),
+            "--edges" => {
+                let value = value()?;
+                let (layer, path) = value
+                    .split_once('=')
+                    .ok_or(format!("Expected LAYER=FILE for --edges, found {}", value))?;
+                args.edges.push((layer.to_string(), path.to_string()));
+            }
+            "--replicas" => {
+                args.replicas = Some(
+                    value()?
+                        .parse()
+                        .map_err(|e| format!("Invalid number of replicas: {}", e))?,
+                )
+            }
+            "--quantiles" => {
+                args.quantiles = value()?
+                    .split(',')
+                    .map(|q| match q.trim().parse::<f64>() {
+                        Ok(q) if (0. ..=1.).contains(&q) => Ok(q),
+                        Ok(q) => Err(format!("{} is not between 0 and 1", q)),
+                        Err(e) => Err(e.to_string()),
+                    })
+                    .collect::<Result<_, _>>()
+                    .map_err(|e| format!("Invalid quantiles: {}", e))?
+            }
+            "-h" | "--help" => {
+                print!("{}", USAGE);
+                process::exit(0);
+            }
+            _ => return Err(format!("Unknown argument {}", arg)),
+        }
+    }
+    if args.nodes.is_none() && !args.edges.is_empty() {
+        return Err("--edges needs --nodes".to_string());
+    }
+    if args.nodes.is_some() && (args.sweep.is_some() || args.replicas.is_some()) {
+        return Err("--nodes can't be combined with --sweep or --replicas".to_string());
+    }
+    Ok(args)
+}
+
+fn load<T: DeserializeOwned>(path: &str) -> Result<T, Box<dyn Error>> {
+    let text = fs::read_to_string(path)?;
+    let res = match Path::new(path).extension().and_then(|e| e.to_str()) {
+        Some("toml") => toml::from_str(&text)?,
+        _ => serde_json::from_str(&text)?,
+    };
+    Ok(res)
+}
+
+// Accept both a bare list and a `policies` key, since TOML files can only have
+// tables at the top level.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PolicyFile {
+    List(Vec<Policy>),
+    Table { policies: Vec<Policy> },
+}
+
+// Write a table in the format given by the extension of `path`.
+fn write_table(path: &str, table: &Table) -> Result<(), Box<dyn Error>> {
+    let mut out = io::BufWriter::new(fs::File::create(path)?);
+    match Path::new(path).extension().and_then(|e| e.to_str()) {
+        Some("csv") => table.write_csv(&mut out)?,
+        #[cfg(feature = "columnar")]
+        Some("arrow") | Some("feather") => table.write_ipc(&mut out)?,
+        #[cfg(feature = "columnar")]
+        Some("parquet") => table.write_parquet(&mut out)?,
+        #[cfg(not(feature = "columnar"))]
+        Some("arrow") | Some("feather") | Some("parquet") => {
+            return Err(format!("Writing {} needs the columnar feature", path).into())
+        }
+        _ => return Err(format!("Unknown table format for {}", path).into()),
+    }
+    out.flush()?;
+    Ok(())
+}
+
+fn write_ensemble_csv<W: Write>(out: &mut W, summary: &EnsembleSummary) -> io::Result<()> {
+    let mut header = vec![
+        "time".to_string(),
+        "quantity".to_string(),
+        "mean".to_string(),
+        "median".to_string(),
+    ];
+    header.extend(summary.quantiles.iter().map(|q| format!("q{}", q)));
+    writeln!(out, "{}", header.join(","))?;
+    let mut write_band = |time: usize, name: &str, band: &Band| {
+        let mut row = vec![
+            time.to_string(),
+            name.to_string(),
+            band.mean.to_string(),
+            band.median.to_string(),
+        ];
+        row.extend(band.quantiles.iter().map(|x| x.to_string()));
+        writeln!(out, "{}", row.join(","))
+    };
+    for day in summary.days.iter() {
+        for (name, band) in Counter::NAMES.iter().zip(day.abs_counter.iter()) {
+            write_band(day.time, name, band)?;
+        }
+        for (name, band) in Counter::NAMES.iter().zip(day.day_counter.iter()) {
+            write_band(day.time, &format!("new_{}", name), band)?;
+        }
+        write_band(day.time, "daily_r", &day.daily_r)?;
+        write_band(
+            day.time,
+            "daily_serial_interval",
+            &day.daily_serial_interval,
+        )?;
+    }
+    Ok(())
+}
+
+fn write_sweep_csv<W: Write>(
+    out: &mut W,
+    spec: &SweepSpec,
+    rows: &[SweepRow],
+) -> Result<(), Box<dyn Error>> {
+    let mut header = vec!["variant".to_string(), "seed".to_string()];
+    header.extend(spec.parameters.iter().map(|p| p.field.clone()));
+    writeln!(
+        out,
+        "{},days,total_deaths,peak_severe,peak_severe_day,peak_infected,peak_infected_day,attack_rate",
+        header.join(",")
+    )?;
+    for row in rows {
+        let mut line = vec![row.variant.to_string(), row.seed.to_string()];
+        line.extend(
+            spec.parameters
+                .iter()
+                .map(|p| row.parameters[&p.field].to_string()),
+        );
+        let o = &row.outcome;
+        writeln!(
+            out,
+            "{},{},{},{},{},{},{},{}",
+            line.join(","),
+            o.days,
+            o.total_deaths,
+            o.peak_severe,
+            o.peak_severe_day,
+            o.peak_infected,
+            o.peak_infected_day,
+            o.attack_rate
+        )?;
+    }
+    Ok(())
+}
+
+fn write_branches_csv<W: Write>(out: &mut W, report: &BranchReport) -> io::Result<()> {
+    writeln!(
+        out,
+        "branch,days,deaths,peak_severe,peak_severe_day,attack_rate,\
+         deaths_diff,peak_severe_diff,attack_rate_diff"
+    )?;
+    for (b, d) in report.branches.iter().zip(report.diffs.iter()) {
+        writeln!(
+            out,
+            "{},{},{},{},{},{},{},{},{}",
+            b.name,
+            b.days,
+            b.deaths,
+            b.peak_severe,
+            b.peak_severe_day,
+            b.attack_rate,
+            d.deaths,
+            d.peak_severe,
+            d.attack_rate
+        )?;
+    }
+    Ok(())
+}
+
+fn write_json(path: &str, records: &[HistoryEntry]) -> Result<(), Box<dyn Error>> {
+    let mut out = io::BufWriter::new(fs::File::create(path)?);
+    serde_json::to_writer(&mut out, records)?;
+    writeln!(out)?;
+    out.flush()?;
+    Ok(())
+}
+
+fn write_transmissions(path: &str, tree: &TransmissionTree) -> Result<(), Box<dyn Error>> {
+    let mut out = io::BufWriter::new(fs::File::create(path)?);
+    match Path::new(path).extension().and_then(|e| e.to_str()) {
+        Some("csv") => tree.write_csv(&mut out)?,
+        Some("nwk") | Some("newick") => out.write_all(tree.to_newick().as_bytes())?,
+        Some("json") => serde_json::to_writer(&mut out, &tree.to_json())?,
+        _ => return Err(format!("Unknown transmission tree format for {}", path).into()),
+    }
+    out.flush()?;
+    Ok(())
+}
+
+fn write_networks(dir: &Path, simulation: &Simulation) -> Result<(), Box<dyn Error>> {
+    fs::create_dir_all(dir)?;
+    let mut out = io::BufWriter::new(fs::File::create(dir.join("nodes.csv"))?);
+    simulation.node_table().write_csv(&mut out)?;
+    out.flush()?;
+    for layer in simulation.layer_names() {
+        let mut out = io::BufWriter::new(fs::File::create(dir.join(format!("{}.csv", layer)))?);
+        simulation.edge_table(&layer).unwrap().write_csv(&mut out)?;
+        out.flush()?;
+    }
+    let mut out = io::BufWriter::new(fs::File::create(dir.join("contacts.graphml"))?);
+    simulation.write_graphml(&mut out)?;
+    out.flush()?;
+    let mut out = io::BufWriter::new(fs::File::create(dir.join("stats.json"))?);
+    serde_json::to_writer_pretty(&mut out, &simulation.network_stats())?;
+    out.flush()?;
+    Ok(())
+}
+
+fn invalid_config(errors: Vec<ConfigError>) -> Box<dyn Error> {
+    let messages: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+    format!("Invalid configuration:\n  {}", messages.join("\n  ")).into()
+}
+
+fn run(args: Args) -> Result<(), Box<dyn Error>> {
+    let config: Config = match &args.config {
+        Some(path) => load(path)?,
+        None => Config::default(),
+    };
+    let policies = match &args.policies {
+        Some(path) => match load(path)? {
+            PolicyFile::List(l) => l,
+            PolicyFile::Table { policies } => policies,
+        },
+        None => Vec::new(),
+    };
+
+    let stdout = io::stdout();
+    let mut out = io::BufWriter::new(stdout.lock());
+
+    if let Some(path) = &args.sweep {
+        let mut spec: SweepSpec = load(path)?;
+        if let Some(days) = args.days {
+            spec.days = days;
+        }
+        let rows = run_sweep(&config, &policies, &spec)?;
+        match args.format {
+            Format::Csv => write_sweep_csv(&mut out, &spec, &rows)?,
+            Format::Json => {
+                serde_json::to_writer(&mut out, &rows)?;
+                writeln!(out)?;
+            }
+        }
+        out.flush()?;
+        return Ok(());
+    }
+
+    let days = args.days.unwrap_or(365);
+    if let Some(replicas) = args.replicas {
+        let spec = EnsembleSpec {
+            replicas,
+            days,
+            quantiles: args.quantiles,
+        };
+        let summary = run_ensemble(&config, &policies, &spec).map_err(invalid_config)?;
+        match args.format {
+            Format::Csv => write_ensemble_csv(&mut out, &summary)?,
+            Format::Json => {
+                serde_json::to_writer(&mut out, &summary)?;
+                writeln!(out)?;
+            }
+        }
+        out.flush()?;
+        return Ok(());
+    }
+
+    let mut simulation = match &args.nodes {
+        Some(path) => {
+            let mut networks = ImportedNetworks::from_nodes(&fs::read_to_string(path)?)?;
+            for (layer, path) in args.edges.iter() {
+                networks.add_layer(layer, &fs::read_to_string(path)?)?;
+            }
+            Simulation::from_networks(config, &networks)?
+        }
+        None => Simulation::try_new(config).map_err(invalid_config)?,
+    };
+    for p in policies {
+        simulation.add_policy(p).map_err(invalid_config)?;
+    }
+
+    if let Some(path) = &args.branches {
+        let mut spec: BranchSpec = load(path)?;
+        if let Some(days) = args.days {
+            spec.days = days;
+        }
+        while simulation.get_time() < spec.fork_day {
+            simulation.tick();
+        }
+        let report = simulation
+            .run_branches(&spec.scenarios, spec.days)
+            .map_err(invalid_config)?;
+        match args.format {
+            Format::Csv => write_branches_csv(&mut out, &report)?,
+            Format::Json => {
+                serde_json::to_writer(&mut out, &report)?;
+                writeln!(out)?;
+            }
+        }
+        out.flush()?;
+        return Ok(());
+    }
+
+    if args.transmissions.is_some() {
+        simulation.record_infections();
+    }
+    let mut records = vec![simulation.history_entry(&Metric::ALL)];
+    while simulation.get_time() < days && simulation.counter().active_infections() > 0 {
+        simulation.tick();
+        records.push(simulation.history_entry(&Metric::ALL));
+    }
+
+    match &args.output {
+        Some(path) if path.ends_with(".json") => write_json(path, &records)?,
+        Some(path) => write_table(path, &Table::daily(&records))?,
+        None => {
+            match args.format {
+                Format::Csv => Table::daily(&records).write_csv(&mut out)?,
+                Format::Json => {
+                    serde_json::to_writer(&mut out, &records)?;
+                    writeln!(out)?;
+                }
+            }
+            out.flush()?;
+        }
+    }
+    if let Some(path) = &args.agents {
+        write_table(path, &simulation.agent_table())?;
+    }
+    if let Some(path) = &args.transmissions {
+        write_transmissions(path, &simulation.transmission_tree())?;
+    }
+    if let Some(dir) = &args.networks {
+        write_networks(Path::new(dir), &simulation)?;
+    }
+    Ok(())
+}
+
+fn main() {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            process::exit(2);
+        }
+    };
+    if let Err(e) = run(args) {
+        eprintln!("Error: {}", e);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,204 @@
+use gloo_utils::format::JsValueSerdeExt;
+use rand::SeedableRng;
+use rand_pcg::Pcg64;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+use crate::validate::ErrorMessage;
+use crate::{ConfigError, Policy, Simulation, State};
+
+// Policies added to a branch on top of the ones it inherits.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Scenario {
+    pub name: String,
+    #[serde(default)]
+    pub policies: Vec<Policy>,
+    // Reseed the branch. Without a seed every branch draws the same random
+    // numbers as long as the policies don't change what is drawn, so the
+    // differences come from the policies rather than from chance.
+    #[serde(default)]
+    pub seed: Option<u64>,
+}
+
+fn default_days() -> usize {
+    365
+}
+
+// Run a simulation until `fork_day` and then run a branch per scenario until
+// `days`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BranchSpec {
+    pub fork_day: usize,
+    #[serde(default = "default_days")]
+    pub days: usize,
+    pub scenarios: Vec<Scenario>,
+}
+
+// What happens in a branch from the day it is forked.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BranchOutcome {
+    pub name: String,
+    // Day of the last tick.
+    pub days: usize,
+    // Deaths since the fork.
+    pub deaths: i32,
+    // Largest number of Severe agents, i.e. of occupied hospital beds, and
+    // the day it happens.
+    pub peak_severe: i32,
+    pub peak_severe_day: usize,
+    // Infections since the fork per agent, counting reinfections.
+    pub attack_rate: f64,
+}
+
+// The difference between a branch and the first one.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BranchDiff {
+    pub name: String,
+    pub deaths: i32,
+    pub peak_severe: i32,
+    pub attack_rate: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BranchReport {
+    pub fork_day: usize,
+    pub branches: Vec<BranchOutcome>,
+    pub diffs: Vec<BranchDiff>,
+}
+
+impl BranchReport {
+    // Compare every branch to the first one, which is the baseline.
+    pub fn new(fork_day: usize, branches: Vec<BranchOutcome>) -> Self {
+        let diffs = match branches.first() {
+            Some(base) => branches
+                .iter()
+                .map(|b| BranchDiff {
+                    name: b.name.clone(),
+                    deaths: b.deaths - base.deaths,
+                    peak_severe: b.peak_severe - base.peak_severe,
+                    attack_rate: b.attack_rate - base.attack_rate,
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+        BranchReport {
+            fork_day,
+            branches,
+            diffs,
+        }
+    }
+}
+
+// A forked simulation that keeps track of its outcome as it runs.
+pub struct Branch {
+    pub simulation: Simulation,
+    outcome: BranchOutcome,
+    initial_deaths: i32,
+    infections: i32,
+}
+
+impl Branch {
+    pub fn new(name: &str, simulation: Simulation) -> Self {
+        let severe = simulation.counter.state_count(State::Severe(0));
+        let outcome = BranchOutcome {
+            name: name.to_string(),
+            days: simulation.time,
+            deaths: 0,
+            peak_severe: severe,
+            peak_severe_day: simulation.time,
+            attack_rate: 0.,
+        };
+        Branch {
+            initial_deaths: simulation.counter.state_count(State::Dead),
+            infections: 0,
+            simulation,
+            outcome,
+        }
+    }
+
+    pub fn tick(&mut self) {
+        let simulation = &mut self.simulation;
+        simulation.tick();
+        self.infections += simulation.new_infections();
+        let severe = simulation.counter.state_count(State::Severe(0));
+        let outcome = &mut self.outcome;
+        if severe > outcome.peak_severe {
+            outcome.peak_severe = severe;
+            outcome.peak_severe_day = simulation.time;
+        }
+        let population: i32 = simulation.counter.abs_counter.iter().sum();
+        outcome.days = simulation.time;
+        outcome.deaths = simulation.counter.state_count(State::Dead) - self.initial_deaths;
+        outcome.attack_rate = self.infections as f64 / population as f64;
+    }
+
+    pub fn outcome(&self) -> &BranchOutcome {
+        &self.outcome
+    }
+}
+
+impl Simulation {
+    // Fork a branch per scenario, run each until `days` and compare them.
+    // Fails if a scenario has policies for layers that don't exist.
+    pub fn run_branches(
+        &self,
+        scenarios: &[Scenario],
+        days: usize,
+    ) -> Result<BranchReport, Vec<ConfigError>> {
+        let errors: Vec<ConfigError> = scenarios
+            .iter()
+            .flat_map(|s| self.config.validate_policies(&s.policies))
+            .collect();
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        let outcomes = scenarios
+            .iter()
+            .map(|scenario| {
+                let mut simulation = self.fork(scenario.seed);
+                for p in scenario.policies.iter() {
+                    simulation.policies.add(p.clone());
+                }
+                let mut branch = Branch::new(&scenario.name, simulation);
+                while branch.simulation.time < days {
+                    branch.tick();
+                }
+                branch.outcome
+            })
+            .collect();
+        Ok(BranchReport::new(self.time, outcomes))
+    }
+}
+
+#[wasm_bindgen]
+impl Simulation {
+    // An independent copy of the simulation as it is now, with its own
+    // policies. With a seed, the copy draws different random numbers from
+    // now on.
+    pub fn fork(&self, seed: Option<u64>) -> Simulation {
+        let mut res = self.clone();
+        if let Some(seed) = seed {
+            res.rng = Pcg64::seed_from_u64(seed);
+            res.config.seed = Some(seed);
+        }
+        res
+    }
+
+    #[wasm_bindgen(js_name = run_branches)]
+    pub fn run_branches_js(&self, scenarios: JsValue, days: usize) -> Result<JsValue, JsValue> {
+        let scenarios: Vec<Scenario> = JsValueSerdeExt::into_serde(&scenarios).map_err(|e| {
+            let errors = vec![ErrorMessage {
+                field: None,
+                message: e.to_string(),
+            }];
+            <JsValue as JsValueSerdeExt>::from_serde(&errors).unwrap()
+        })?;
+        match self.run_branches(&scenarios, days) {
+            Ok(report) => Ok(<JsValue as JsValueSerdeExt>::from_serde(&report).unwrap()),
+            Err(errors) => {
+                let errors: Vec<ErrorMessage> = errors.iter().map(ErrorMessage::from).collect();
+                Err(<JsValue as JsValueSerdeExt>::from_serde(&errors).unwrap())
+            }
+        }
+    }
+}
@@ -0,0 +1,363 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use rand::{Rng, SeedableRng};
+use rand_pcg::Pcg64;
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::{Averager, Counter, Simulation, State, NSTATES};
+
+// A new infection, applied to the agent at the end of the day.
+pub(crate) struct Infection {
+    pub(crate) agent: usize,
+    pub(crate) infector: usize,
+    // Index of the contact layer.
+    pub(crate) layer: usize,
+    pub(crate) infector_state: State,
+    pub(crate) strain: usize,
+    pub(crate) asymptomatic: bool,
+    pub(crate) severe_protection: f64,
+}
+
+// What happens to a group of agents in a day. The agents are updated only
+// reading the simulation, and the changes that involve other agents or the
+// shared counters are collected here and merged once all of them are done.
+pub(crate) struct Day {
+    pub(crate) rng: Pcg64,
+    // The counter at the start of the day plus the transitions of the group.
+    pub(crate) counter: Counter,
+    pub(crate) serial_interval_average: Averager,
+    pub(crate) infections: Vec<Infection>,
+    // Agents that stopped being infectious.
+    pub(crate) recovered: Vec<usize>,
+    // Agents that became Detected, whose contacts are traced.
+    pub(crate) detected: Vec<usize>,
+}
+
+impl Day {
+    fn new(rng: Pcg64, counter: Counter) -> Day {
+        Day {
+            rng,
+            counter,
+            serial_interval_average: Averager::new(),
+            infections: Vec::new(),
+            recovered: Vec::new(),
+            detected: Vec::new(),
+        }
+    }
+}
+
+// The states of the agents at the end of the day, while it is being
+// computed.
+pub(crate) enum NewStates {
+    // Every agent, when they are all updated.
+    All(Vec<State>),
+    // Only the agents whose state changed, on top of `Simulation::states`.
+    Changed(FxHashMap<usize, State>),
+}
+
+impl NewStates {
+    pub(crate) fn get(&self, states: &[State], i: usize) -> State {
+        match self {
+            NewStates::All(newstates) => newstates[i],
+            NewStates::Changed(changed) => changed.get(&i).copied().unwrap_or(states[i]),
+        }
+    }
+
+    pub(crate) fn set(&mut self, i: usize, s: State) {
+        match self {
+            NewStates::All(newstates) => newstates[i] = s,
+            NewStates::Changed(changed) => {
+                changed.insert(i, s);
+            }
+        }
+    }
+}
+
+// What `event_driven` keeps up to date instead of going through every agent
+// each day: the agents that go through transitions every day, and the day
+// the agents in a state that wanes (Immune, ImmuneDetected and Vaccinated)
+// become Susceptible again, drawn when they enter it.
+#[derive(Default, Serialize, Deserialize, Clone)]
+pub(crate) struct ActiveAgents {
+    active: BTreeSet<usize>,
+    // Pairs of day and agent.
+    wakeups: BTreeSet<(usize, usize)>,
+    wakeup_day: BTreeMap<usize, usize>,
+}
+
+// Whether an agent in state `s` can change state on any day by itself.
+fn steps_daily(s: State) -> bool {
+    match s {
+        State::Exposed(_)
+        | State::Infected(_)
+        | State::Asymptomatic(_)
+        | State::Detected(_)
+        | State::Severe(_)
+        | State::Unattended => true,
+        State::Susceptible
+        | State::Immune(_)
+        | State::ImmuneDetected(_)
+        | State::Dead
+        | State::PartiallyVaccinated(_)
+        | State::Vaccinated(_) => false,
+    }
+}
+
+// The number of days until the transition of a state left with the daily
+// probabilities of `profile`, from the day `t` in the state, or None if it
+// never happens. Same as drawing each day, but the last probability, which
+// is used indefinitely, gives a geometric number of days.
+fn sample_days<R: Rng>(rng: &mut R, profile: &[f64], t: usize) -> Option<usize> {
+    let last = profile.len() - 1;
+    for (days, p) in profile[usize::min(t, last)..last].iter().enumerate() {
+        if *p > rng.gen() {
+            return Some(days);
+        }
+    }
+    let days = last.saturating_sub(t);
+    let p = profile[last];
+    if p <= 0. {
+        None
+    } else if p >= 1. {
+        Some(days)
+    } else {
+        let u: f64 = rng.gen();
+        let failures = ((1. - u).ln() / (1. - p).ln()).floor();
+        Some(days.saturating_add(failures as usize))
+    }
+}
+
+impl Simulation {
+    #[cfg(not(feature = "parallel"))]
+    pub(crate) fn update_agents(&mut self) -> (NewStates, Vec<Day>) {
+        let rng = std::mem::replace(&mut self.rng, Pcg64::seed_from_u64(0));
+        let mut day = Day::new(rng, self.counter.clone());
+        let newstates = (0..self.states.len())
+            .map(|i| self.update_agent(&mut day, i))
+            .collect();
+        std::mem::swap(&mut self.rng, &mut day.rng);
+        (NewStates::All(newstates), vec![day])
+    }
+
+    // Split the agents in one chunk per thread, each with its own random
+    // stream seeded from the simulation one, so that the results depend only
+    // on the seed and the number of threads. The agents in a chunk only see
+    // the transitions of the same chunk when checking the hospital capacity.
+    #[cfg(feature = "parallel")]
+    pub(crate) fn update_agents(&mut self) -> (NewStates, Vec<Day>) {
+        use rayon::prelude::*;
+
+        let n = self.states.len();
+        let nchunks = rayon::current_num_threads();
+        let chunk_size = usize::max(n.div_ceil(nchunks), 1);
+        let seeds: Vec<u64> = (0..nchunks).map(|_| self.rng.gen()).collect();
+        let mut newstates = vec![State::Susceptible; n];
+        let simulation = &*self;
+        let days = newstates
+            .par_chunks_mut(chunk_size)
+            .zip(seeds.par_iter())
+            .enumerate()
+            .map(|(c, (chunk, seed))| {
+                let mut day = Day::new(Pcg64::seed_from_u64(*seed), simulation.counter.clone());
+                for (k, s) in chunk.iter_mut().enumerate() {
+                    *s = simulation.update_agent(&mut day, c * chunk_size + k);
+                }
+                day
+            })
+            .collect();
+        (NewStates::All(newstates), days)
+    }
+
+    // Used with `event_driven`. The infectious agents try to infect their
+    // contacts, then the agents that step every day and have not just been
+    // infected go through their transitions, and the agents whose immunity
+    // wanes today become Susceptible.
+    pub(crate) fn update_active_agents(&mut self) -> (NewStates, Vec<Day>) {
+        let rng = std::mem::replace(&mut self.rng, Pcg64::seed_from_u64(0));
+        let mut day = Day::new(rng, self.counter.clone());
+        let mut changed: FxHashMap<usize, State> = FxHashMap::default();
+        for n in self.active.active.iter().copied() {
+            if !matches!(
+                self.states[n],
+                State::Infected(_) | State::Asymptomatic(_) | State::Detected(_)
+            ) {
+                continue;
+            }
+            for (k, layer) in self.layers.iter().enumerate() {
+                let infected_coef = layer.undetected_coef();
+                let detected_coef = layer.detected_coef();
+                for (i, w) in layer.graph.iterweighted(n) {
+                    let s = self.states[i];
+                    // Skip the contacts already infected today.
+                    if !self.can_be_infected(s)
+                        || changed.contains_key(&i)
+                        || !self.uses_layer(k, i)
+                    {
+                        continue;
+                    }
+                    let p = self
+                        .infection_probability(
+                            i,
+                            s,
+                            self.susceptibility(i, s),
+                            n,
+                            w * infected_coef,
+                            w * detected_coef,
+                        )
+                        .unwrap();
+                    if p > day.rng.gen() {
+                        changed.insert(i, self.infect(&mut day, i, s, n, k));
+                    }
+                }
+            }
+        }
+        for i in self.active.active.iter().copied() {
+            changed
+                .entry(i)
+                .or_insert_with(|| self.transit_agent(&mut day, i));
+        }
+        for (_, i) in self.active.wakeups.range(..=(self.time, usize::MAX)) {
+            if !changed.contains_key(i) {
+                day.counter.transit(self.states[*i], State::Susceptible);
+                changed.insert(*i, State::Susceptible);
+            }
+        }
+        std::mem::swap(&mut self.rng, &mut day.rng);
+        (NewStates::Changed(changed), vec![day])
+    }
+
+    // Update what `event_driven` keeps track of for agent `i`, which goes
+    // from `old` to `new` at the end of the day.
+    pub(crate) fn track(&mut self, i: usize, old: State, new: State) {
+        if !self.config.event_driven {
+            return;
+        }
+        if steps_daily(new) {
+            self.active.active.insert(i);
+        } else {
+            self.active.active.remove(&i);
+        }
+        // Detecting an Immune agent does not change its immunity.
+        if let (State::Immune(_), State::ImmuneDetected(_)) = (old, new) {
+            return;
+        }
+        if let Some(day) = self.active.wakeup_day.remove(&i) {
+            self.active.wakeups.remove(&(day, i));
+        }
+        let (profile, t) = match new {
+            State::Immune(t) | State::ImmuneDetected(t) => {
+                (&self.config.immune_susceptible_profile, t)
+            }
+            State::Vaccinated(t) => (&self.config.vaccinated_susceptible_profile, t),
+            _ => return,
+        };
+        if let Some(days) = sample_days(&mut self.rng, profile, t) {
+            // The first transition is drawn on the next day.
+            let day = (self.time + 1).saturating_add(days);
+            self.active.wakeups.insert((day, i));
+            self.active.wakeup_day.insert(i, day);
+        }
+    }
+
+    // Move agent `i` to `new` after the agents have been updated, when it is
+    // traced, vaccinated or seeded. If it had already changed state today,
+    // it went through one more state, which the per age and per strain
+    // counters don't see in the change from the old state to the new one.
+    pub(crate) fn set_state(&mut self, newstates: &mut NewStates, i: usize, new: State) {
+        let s = newstates.get(&self.states, i);
+        self.counter.transit(s, new);
+        if s.index() != self.states[i].index() {
+            self.passed_states.push((i, s, self.strain[i]));
+        }
+        newstates.set(i, new);
+    }
+
+    // Replace the states by the new ones, and update the per age and per
+    // strain counters and what `event_driven` keeps track of from the agents
+    // whose state changed.
+    pub(crate) fn apply_states(&mut self, newstates: NewStates) {
+        let mut changes: Vec<(usize, State, State)> = match newstates {
+            NewStates::All(newstates) => {
+                let changes = self
+                    .states
+                    .iter()
+                    .zip(newstates.iter())
+                    .enumerate()
+                    .filter(|(_, (old, new))| old.index() != new.index())
+                    .map(|(i, (old, new))| (i, *old, *new))
+                    .collect();
+                self.states = newstates;
+                changes
+            }
+            NewStates::Changed(changed) => changed
+                .into_iter()
+                .filter_map(|(i, new)| {
+                    let old = std::mem::replace(&mut self.states[i], new);
+                    if old.index() != new.index() {
+                        Some((i, old, new))
+                    } else {
+                        None
+                    }
+                })
+                .collect(),
+        };
+        changes.sort_unstable_by_key(|(i, _, _)| *i);
+        for (i, old, new) in changes.iter() {
+            self.age_counters[self.ages[*i]].transit(*old, *new);
+        }
+        for (i, s, strain) in std::mem::take(&mut self.passed_states) {
+            self.age_counters[self.ages[i]].day_counter[s.index()] += 1;
+            if let Some(strain) = strain {
+                self.strain_counters[strain].day_counter[s.index()] += 1;
+            }
+        }
+        self.update_strain_counters(&changes);
+        for (i, old, new) in changes {
+            self.track(i, old, new);
+        }
+    }
+
+    // Apply the changes collected while updating the agents, in the order of
+    // the agents.
+    pub(crate) fn merge_days(&mut self, days: Vec<Day>) {
+        let start = self.counter.clone();
+        for day in days.iter() {
+            for k in 0..NSTATES {
+                self.counter.abs_counter[k] += day.counter.abs_counter[k] - start.abs_counter[k];
+                self.counter.day_counter[k] += day.counter.day_counter[k];
+            }
+            self.serial_interval_average
+                .merge(&day.serial_interval_average);
+            for infection in day.infections.iter() {
+                let i = infection.agent;
+                self.set_strain(i, infection.strain);
+                self.asymptomatic[i] = infection.asymptomatic;
+                self.severe_protection[i] = infection.severe_protection;
+                self.infections_caused[infection.infector] += 1;
+                let detected = matches!(infection.infector_state, State::Detected(_));
+                for c in [
+                    &mut self.counter,
+                    &mut self.age_counters[self.ages[i]],
+                    &mut self.strain_counters[infection.strain],
+                ]
+                .iter_mut()
+                {
+                    c.by_network.register(infection.layer, detected);
+                }
+            }
+            self.log_infections(&day.infections);
+        }
+        // All of today's infections have to be counted before the R of the
+        // agents that stop being infectious.
+        for day in days.iter() {
+            for i in day.recovered.iter() {
+                self.r_average.push(self.infections_caused[*i] as i32);
+                self.infections_caused[*i] = 0;
+            }
+            for i in day.detected.iter() {
+                self.queue_contact_tracing(*i);
+            }
+        }
+    }
+}
@@ -0,0 +1,200 @@
+use gloo_utils::format::JsValueSerdeExt;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+use crate::validate::{ErrorMessage, Reason, Validator};
+use crate::{Config, ConfigError, Counter, Policy, Simulation};
+
+pub const DEFAULT_QUANTILES: [f64; 4] = [0.05, 0.25, 0.75, 0.95];
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EnsembleSpec {
+    pub replicas: usize,
+    pub days: usize,
+    #[serde(default = "default_quantiles")]
+    pub quantiles: Vec<f64>,
+}
+
+fn default_quantiles() -> Vec<f64> {
+    DEFAULT_QUANTILES.to_vec()
+}
+
+impl EnsembleSpec {
+    // There must be at least one replica and the quantiles must be between 0
+    // and 1. The problems are reported like those of the configuration.
+    pub fn validate(&self) -> Vec<ConfigError> {
+        let mut v = Validator::default();
+        if self.replicas == 0 {
+            v.push("replicas", Reason::NotPositive { value: 0. });
+        }
+        for q in self.quantiles.iter() {
+            v.probability("quantiles", *q);
+        }
+        v.finish()
+    }
+}
+
+// Summary statistics of one quantity over the replicas. Replicas where the
+// quantity is not defined (e.g. the daily R on a day nobody recovered) are
+// ignored. All the values are NaN if no replica has it defined.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Band {
+    pub mean: f64,
+    pub median: f64,
+    pub quantiles: Vec<f64>,
+}
+
+impl Band {
+    fn new(values: &mut Vec<f64>, quantiles: &[f64]) -> Band {
+        values.retain(|x| !x.is_nan());
+        values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let mean = values.iter().sum::<f64>() / values.len() as f64;
+        Band {
+            mean,
+            median: quantile(values, 0.5),
+            quantiles: quantiles.iter().map(|q| quantile(values, *q)).collect(),
+        }
+    }
+}
+
+// Linear interpolation between the closest ranks of sorted values.
+fn quantile(sorted: &[f64], q: f64) -> f64 {
+    if sorted.is_empty() {
+        return f64::NAN;
+    }
+    let pos = q * (sorted.len() - 1) as f64;
+    let lo = pos.floor() as usize;
+    let hi = pos.ceil() as usize;
+    sorted[lo] + (sorted[hi] - sorted[lo]) * (pos - lo as f64)
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct DaySummary {
+    pub time: usize,
+    pub abs_counter: Vec<Band>,
+    pub day_counter: Vec<Band>,
+    pub daily_r: Band,
+    pub daily_serial_interval: Band,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct EnsembleSummary {
+    pub replicas: usize,
+    pub quantiles: Vec<f64>,
+    pub days: Vec<DaySummary>,
+}
+
+struct Snapshot {
+    counter: Counter,
+    daily_r: f64,
+    daily_serial_interval: f64,
+}
+
+impl Snapshot {
+    fn new(simulation: &Simulation) -> Self {
+        Snapshot {
+            counter: simulation.counter().clone(),
+            daily_r: simulation.get_daily_r(),
+            daily_serial_interval: simulation.get_daily_serial_interval(),
+        }
+    }
+}
+
+fn run_replica(config: Config, policies: &[Policy], days: usize) -> Vec<Snapshot> {
+    let mut simulation = Simulation::new(config);
+    // The policies have been validated with the configuration.
+    for p in policies {
+        simulation.policies.add(p.clone());
+    }
+    let mut res = Vec::with_capacity(days + 1);
+    res.push(Snapshot::new(&simulation));
+    for _ in 0..days {
+        simulation.tick();
+        res.push(Snapshot::new(&simulation));
+    }
+    res
+}
+
+// Seeds for each replica. They are derived from the seed in the configuration
+// so that the whole ensemble is reproducible.
+fn replica_seeds(config: &Config, replicas: usize) -> Vec<u64> {
+    let mut rng = config.rng();
+    (0..replicas).map(|_| rng.gen()).collect()
+}
+
+// Run independent replicas of the same configuration and policies, each for
+// the same number of days, and summarize the spread of the results.
+pub fn run_ensemble(
+    config: &Config,
+    policies: &[Policy],
+    spec: &EnsembleSpec,
+) -> Result<EnsembleSummary, Vec<ConfigError>> {
+    let mut errors = config.validate();
+    errors.extend(config.validate_policies(policies));
+    errors.extend(spec.validate());
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    let runs: Vec<Vec<Snapshot>> = replica_seeds(config, spec.replicas)
+        .into_iter()
+        .map(|seed| {
+            let mut c = config.clone();
+            c.seed = Some(seed);
+            run_replica(c, policies, spec.days)
+        })
+        .collect();
+
+    let q = &spec.quantiles;
+    let band = |f: &dyn Fn(&Snapshot) -> f64, day: usize| {
+        let mut values: Vec<f64> = runs.iter().map(|r| f(&r[day])).collect();
+        Band::new(&mut values, q)
+    };
+    let days = (0..=spec.days)
+        .map(|day| DaySummary {
+            time: day,
+            abs_counter: (0..Counter::NAMES.len())
+                .map(|i| band(&|s| s.counter.abs_counter[i] as f64, day))
+                .collect(),
+            day_counter: (0..Counter::NAMES.len())
+                .map(|i| band(&|s| s.counter.day_counter[i] as f64, day))
+                .collect(),
+            daily_r: band(&|s| s.daily_r, day),
+            daily_serial_interval: band(&|s| s.daily_serial_interval, day),
+        })
+        .collect();
+    Ok(EnsembleSummary {
+        replicas: spec.replicas,
+        quantiles: spec.quantiles.clone(),
+        days,
+    })
+}
+
+// Same as `run_ensemble`, taking and returning JavaScript objects. On failure,
+// return a list of `{field, message}` objects as `Simulation.from_js` does.
+#[wasm_bindgen]
+pub fn run_ensemble_js(
+    config: JsValue,
+    policies: JsValue,
+    spec: JsValue,
+) -> Result<JsValue, JsValue> {
+    let to_js = |e: String| {
+        let errors = vec![ErrorMessage {
+            field: None,
+            message: e,
+        }];
+        <JsValue as JsValueSerdeExt>::from_serde(&errors).unwrap()
+    };
+    let config: Config = JsValueSerdeExt::into_serde(&config).map_err(|e| to_js(e.to_string()))?;
+    let policies: Vec<Policy> =
+        JsValueSerdeExt::into_serde(&policies).map_err(|e| to_js(e.to_string()))?;
+    let spec: EnsembleSpec =
+        JsValueSerdeExt::into_serde(&spec).map_err(|e| to_js(e.to_string()))?;
+    match run_ensemble(&config, &policies, &spec) {
+        Ok(summary) => Ok(<JsValue as JsValueSerdeExt>::from_serde(&summary).unwrap()),
+        Err(errors) => {
+            let errors: Vec<ErrorMessage> = errors.iter().map(ErrorMessage::from).collect();
+            Err(<JsValue as JsValueSerdeExt>::from_serde(&errors).unwrap())
+        }
+    }
+}
@@ -0,0 +1,339 @@
+use std::io::{self, Write};
+
+use crate::{Counter, Generator, HistoryEntry, PolicyKind, Simulation};
+
+pub enum ColumnData {
+    Int(Vec<i64>),
+    Float(Vec<f64>),
+    Bool(Vec<bool>),
+    // Missing values are written as empty fields in CSV and as nulls in Arrow.
+    Str(Vec<Option<String>>),
+}
+
+impl ColumnData {
+    pub fn len(&self) -> usize {
+        match self {
+            ColumnData::Int(v) => v.len(),
+            ColumnData::Float(v) => v.len(),
+            ColumnData::Bool(v) => v.len(),
+            ColumnData::Str(v) => v.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn write_value(&self, row: usize, out: &mut String) {
+        match self {
+            ColumnData::Int(v) => out.push_str(&v[row].to_string()),
+            ColumnData::Float(v) => out.push_str(&v[row].to_string()),
+            ColumnData::Bool(v) => out.push_str(&v[row].to_string()),
+            ColumnData::Str(v) => {
+                if let Some(s) = &v[row] {
+                    if s.contains([',', '"', '\n']) {
+                        out.push('"');
+                        out.push_str(&s.replace('"', "\"\""));
+                        out.push('"');
+                    } else {
+                        out.push_str(s);
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub struct Column {
+    pub name: String,
+    pub data: ColumnData,
+}
+
+// A set of columns of the same length, to be written as CSV or, with the
+// `columnar` feature, as Arrow IPC or Parquet.
+pub struct Table {
+    pub columns: Vec<Column>,
+}
+
+impl Table {
+    // One row per entry of the history, with the columns of the metrics that
+    // every entry has: the number of agents in each state and the new ones,
+    // the new and cumulative infections by network, the daily R and serial
+    // interval and whether each kind of policy is in effect.
+    pub fn daily(history: &[HistoryEntry]) -> Table {
+        let mut table = Table {
+            columns: Vec::new(),
+        };
+        table.push(
+            "time",
+            ColumnData::Int(history.iter().map(|e| e.time as i64).collect()),
+        );
+        let counters: Option<Vec<&Counter>> = history.iter().map(|e| e.counter.as_ref()).collect();
+        if let Some(counters) = counters {
+            let int_column = |f: &dyn Fn(&Counter) -> i32| {
+                ColumnData::Int(counters.iter().map(|c| f(c) as i64).collect())
+            };
+            for (k, name) in Counter::NAMES.iter().enumerate() {
+                table.push(name, int_column(&|c| c.abs_counter[k]));
+            }
+            for (k, name) in Counter::NAMES.iter().enumerate() {
+                table.push(&format!("new_{}", name), int_column(&|c| c.day_counter[k]));
+            }
+            // Every counter has the same contact layers.
+            let network_names = counters
+                .first()
+                .map(|c| c.by_network.names())
+                .unwrap_or_default();
+            for (k, name) in network_names.iter().enumerate() {
+                table.push(
+                    &format!("new_infections_{}", name),
+                    int_column(&|c| c.by_network.day_counter[k]),
+                );
+            }
+            for (k, name) in network_names.iter().enumerate() {
+                table.push(
+                    &format!("infections_{}", name),
+                    int_column(&|c| c.by_network.total_counter[k]),
+                );
+            }
+        }
+        let daily_r: Option<Vec<f64>> = history.iter().map(|e| e.daily_r).collect();
+        if let Some(daily_r) = daily_r {
+            table.push("daily_r", ColumnData::Float(daily_r));
+        }
+        let serial: Option<Vec<f64>> = history.iter().map(|e| e.daily_serial_interval).collect();
+        if let Some(serial) = serial {
+            table.push("daily_serial_interval", ColumnData::Float(serial));
+        }
+        let active: Option<Vec<&Vec<&str>>> =
+            history.iter().map(|e| e.active_policies.as_ref()).collect();
+        if let Some(active) = active {
+            for name in PolicyKind::NAMES.iter() {
+                table.push(
+                    &format!("policy_{}", name.replace('-', "_")),
+                    ColumnData::Bool(active.iter().map(|a| a.contains(name)).collect()),
+                );
+            }
+        }
+        table
+    }
+
+    pub fn push(&mut self, name: &str, data: ColumnData) {
+        self.columns.push(Column {
+            name: name.to_string(),
+            data,
+        });
+    }
+
+    pub fn nrows(&self) -> usize {
+        self.columns.first().map(|c| c.data.len()).unwrap_or(0)
+    }
+
+    pub fn write_csv<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let header: Vec<&str> = self.columns.iter().map(|c| &c.name[..]).collect();
+        writeln!(out, "{}", header.join(","))?;
+        let mut line = String::new();
+        for row in 0..self.nrows() {
+            line.clear();
+            for (k, column) in self.columns.iter().enumerate() {
+                if k > 0 {
+                    line.push(',');
+                }
+                column.data.write_value(row, &mut line);
+            }
+            writeln!(out, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "columnar")]
+mod columnar {
+    use std::io::Write;
+    use std::sync::Arc;
+
+    use arrow::array::{ArrayRef, BooleanArray, Float64Array, Int64Array, StringArray};
+    use arrow::datatypes::{DataType, Field, Schema};
+    use arrow::error::ArrowError;
+    use arrow::ipc::writer::FileWriter;
+    use arrow::record_batch::RecordBatch;
+    use parquet::arrow::ArrowWriter;
+    use parquet::errors::ParquetError;
+
+    use super::{ColumnData, Table};
+
+    impl Table {
+        pub fn to_record_batch(&self) -> Result<RecordBatch, ArrowError> {
+            let mut fields = Vec::with_capacity(self.columns.len());
+            let mut arrays: Vec<ArrayRef> = Vec::with_capacity(self.columns.len());
+            for column in self.columns.iter() {
+                let (datatype, array): (DataType, ArrayRef) = match &column.data {
+                    ColumnData::Int(v) => (DataType::Int64, Arc::new(Int64Array::from(v.clone()))),
+                    ColumnData::Float(v) => {
+                        (DataType::Float64, Arc::new(Float64Array::from(v.clone())))
+                    }
+                    ColumnData::Bool(v) => {
+                        (DataType::Boolean, Arc::new(BooleanArray::from(v.clone())))
+                    }
+                    ColumnData::Str(v) => (
+                        DataType::Utf8,
+                        Arc::new(v.iter().map(|s| s.as_deref()).collect::<StringArray>()),
+                    ),
+                };
+                let nullable = array.null_count() > 0;
+                fields.push(Field::new(&column.name, datatype, nullable));
+                arrays.push(array);
+            }
+            RecordBatch::try_new(Arc::new(Schema::new(fields)), arrays)
+        }
+
+        // Write the table as an Arrow IPC file (also known as Feather v2).
+        pub fn write_ipc<W: Write>(&self, out: W) -> Result<(), ArrowError> {
+            let batch = self.to_record_batch()?;
+            let mut writer = FileWriter::try_new(out, &batch.schema())?;
+            writer.write(&batch)?;
+            writer.finish()
+        }
+
+        pub fn write_parquet<W: Write + Send>(&self, out: W) -> Result<(), ParquetError> {
+            let batch = self.to_record_batch()?;
+            let mut writer = ArrowWriter::try_new(out, batch.schema(), None)?;
+            writer.write(&batch)?;
+            writer.close()?;
+            Ok(())
+        }
+    }
+}
+
+impl Simulation {
+    // One row per agent with its current state, age band, the strain of its
+    // current or last infection (empty if it has never been infected) and
+    // the number of infections it has caused.
+    pub fn agent_table(&self) -> Table {
+        let strain_name = |s: usize| {
+            if s == 0 {
+                crate::ORIGINAL_STRAIN.to_string()
+            } else {
+                self.config.strains[s - 1].name.clone()
+            }
+        };
+        let mut table = Table {
+            columns: Vec::new(),
+        };
+        table.push(
+            "agent",
+            ColumnData::Int((0..self.states.len() as i64).collect()),
+        );
+        table.push(
+            "state",
+            ColumnData::Str(
+                self.states
+                    .iter()
+                    .map(|s| Some(Counter::NAMES[s.index()].to_string()))
+                    .collect(),
+            ),
+        );
+        table.push(
+            "age_band",
+            ColumnData::Str(
+                self.ages
+                    .iter()
+                    .map(|a| Some(self.config.age_bands[*a].name.clone()))
+                    .collect(),
+            ),
+        );
+        table.push(
+            "strain",
+            ColumnData::Str(self.strain.iter().map(|s| s.map(strain_name)).collect()),
+        );
+        table.push(
+            "infections_caused",
+            ColumnData::Int(self.infections_caused.iter().map(|x| *x as i64).collect()),
+        );
+        table
+    }
+}
+
+impl Simulation {
+    // One row per agent with its household, its group in each of the other
+    // layers that have groups (e.g. its workplace), empty outside the layer,
+    // its age band and its current state. The columns are the ones read by
+    // `ImportedNetworks::from_nodes`.
+    pub fn node_table(&self) -> Table {
+        let mut table = Table {
+            columns: Vec::new(),
+        };
+        table.push(
+            "id",
+            ColumnData::Int((0..self.states.len() as i64).collect()),
+        );
+        // The family layer is always the first one.
+        let (family, others) = self.layers.split_first().unwrap();
+        debug_assert!(matches!(family.spec.generator, Generator::Household));
+        table.push(
+            "household",
+            ColumnData::Int(
+                family
+                    .groups
+                    .iter()
+                    .map(|g| g.expect("Every agent has a household") as i64)
+                    .collect(),
+            ),
+        );
+        for layer in others.iter().filter(|l| !l.groups.is_empty()) {
+            table.push(
+                &layer.spec.name,
+                ColumnData::Str(
+                    layer
+                        .groups
+                        .iter()
+                        .map(|g| g.map(|g| g.to_string()))
+                        .collect(),
+                ),
+            );
+        }
+        table.push(
+            "age",
+            ColumnData::Str(
+                self.ages
+                    .iter()
+                    .map(|a| Some(self.config.age_bands[*a].name.clone()))
+                    .collect(),
+            ),
+        );
+        table.push(
+            "state",
+            ColumnData::Str(
+                self.states
+                    .iter()
+                    .map(|s| Some(Counter::NAMES[s.index()].to_string()))
+                    .collect(),
+            ),
+        );
+        table
+    }
+
+    // The current links of a layer, with the agents in `source` and `target`
+    // and the weight of the link, which is 1 unless it was imported with a
+    // different one.
+    pub fn edge_table(&self, layer: &str) -> Option<Table> {
+        let k = self.layer_index(layer)?;
+        let (edges, weights) = self.layers[k].graph.edges();
+        let mut table = Table {
+            columns: Vec::new(),
+        };
+        table.push(
+            "source",
+            ColumnData::Int(edges.iter().map(|e| e.0 as i64).collect()),
+        );
+        table.push(
+            "target",
+            ColumnData::Int(edges.iter().map(|e| e.1 as i64).collect()),
+        );
+        table.push(
+            "weight",
+            ColumnData::Float(weights.unwrap_or_else(|| vec![1.; edges.len()])),
+        );
+        Some(table)
+    }
+}
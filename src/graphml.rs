@@ -0,0 +1,86 @@
+use std::io::{self, Write};
+
+use crate::{ColumnData, Simulation};
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn graphml_type(data: &ColumnData) -> &'static str {
+    match data {
+        ColumnData::Int(_) => "long",
+        ColumnData::Float(_) => "double",
+        ColumnData::Bool(_) => "boolean",
+        ColumnData::Str(_) => "string",
+    }
+}
+
+// The value of a row, or None if it is missing.
+fn graphml_value(data: &ColumnData, row: usize) -> Option<String> {
+    match data {
+        ColumnData::Int(v) => Some(v[row].to_string()),
+        ColumnData::Float(v) => Some(v[row].to_string()),
+        ColumnData::Bool(v) => Some(v[row].to_string()),
+        ColumnData::Str(v) => v[row].as_deref().map(escape),
+    }
+}
+
+impl Simulation {
+    // Every layer as a single undirected GraphML graph. The nodes are the
+    // agents, with the columns of `node_table` as attributes, and each link
+    // has the name of its `layer` and its `weight`.
+    pub fn write_graphml<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let nodes = self.node_table();
+        writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(out, r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#)?;
+        // The first column is the id of the node. The ids of the keys are
+        // positional, as the names of the columns need not be valid ids.
+        for (k, column) in nodes.columns[1..].iter().enumerate() {
+            writeln!(
+                out,
+                r#"  <key id="v{}" for="node" attr.name="{}" attr.type="{}"/>"#,
+                k,
+                escape(&column.name),
+                graphml_type(&column.data)
+            )?;
+        }
+        writeln!(
+            out,
+            r#"  <key id="e_layer" for="edge" attr.name="layer" attr.type="string"/>"#
+        )?;
+        writeln!(
+            out,
+            r#"  <key id="e_weight" for="edge" attr.name="weight" attr.type="double"/>"#
+        )?;
+        writeln!(out, r#"  <graph id="contacts" edgedefault="undirected">"#)?;
+        for row in 0..nodes.nrows() {
+            write!(out, r#"    <node id="n{}">"#, row)?;
+            for (k, column) in nodes.columns[1..].iter().enumerate() {
+                if let Some(value) = graphml_value(&column.data, row) {
+                    write!(out, r#"<data key="v{}">{}</data>"#, k, value)?;
+                }
+            }
+            writeln!(out, "</node>")?;
+        }
+        for layer in self.layers.iter() {
+            let (edges, weights) = layer.graph.edges();
+            let name = escape(&layer.spec.name);
+            for (k, (i, j)) in edges.iter().enumerate() {
+                writeln!(
+                    out,
+                    r#"    <edge source="n{}" target="n{}"><data key="e_layer">{}</data><data key="e_weight">{}</data></edge>"#,
+                    i,
+                    j,
+                    name,
+                    weights.as_ref().map_or(1., |w| w[k])
+                )?;
+            }
+        }
+        writeln!(out, "  </graph>")?;
+        writeln!(out, "</graphml>")?;
+        Ok(())
+    }
+}
@@ -0,0 +1,94 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{policy, AgeCounter, Counter, PolicyEvent, Simulation, StrainCounter};
+
+// The quantities that can be recorded every day.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Metric {
+    Counter,
+    DailyR,
+    DailySerialInterval,
+    PolicyEvents,
+    ActivePolicies,
+    ByAge,
+    ByStrain,
+}
+
+impl Metric {
+    pub const ALL: [Metric; 7] = [
+        Metric::Counter,
+        Metric::DailyR,
+        Metric::DailySerialInterval,
+        Metric::PolicyEvents,
+        Metric::ActivePolicies,
+        Metric::ByAge,
+        Metric::ByStrain,
+    ];
+}
+
+pub(crate) fn default_metrics() -> Vec<Metric> {
+    vec![Metric::Counter, Metric::DailyR, Metric::DailySerialInterval]
+}
+
+// The state of the simulation at the end of a day. Only the metrics in
+// `config.history` are filled.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct HistoryEntry {
+    pub time: usize,
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    pub counter: Option<Counter>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub daily_r: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub daily_serial_interval: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub policy_events: Option<Vec<PolicyEvent>>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "policy::deserialize_names"
+    )]
+    pub active_policies: Option<Vec<&'static str>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub by_age: Option<Vec<AgeCounter>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub by_strain: Option<Vec<StrainCounter>>,
+}
+
+impl Simulation {
+    // One entry per day, starting with the initial state at day 0. Empty when
+    // `config.history` is empty.
+    pub fn history(&self) -> &[HistoryEntry] {
+        &self.history
+    }
+
+    // The entries from `from_day` onwards, e.g. the ones not yet seen by a
+    // consumer.
+    pub fn history_from(&self, from_day: usize) -> impl Iterator<Item = &HistoryEntry> {
+        self.history.iter().skip_while(move |e| e.time < from_day)
+    }
+
+    // The metrics of today, as they would be recorded in the history.
+    pub fn history_entry(&self, metrics: &[Metric]) -> HistoryEntry {
+        let has = |m| metrics.contains(&m);
+        HistoryEntry {
+            time: self.time,
+            counter: has(Metric::Counter).then(|| self.counter.clone()),
+            daily_r: has(Metric::DailyR).then(|| self.r_average.get()),
+            daily_serial_interval: has(Metric::DailySerialInterval)
+                .then(|| self.serial_interval_average.get()),
+            policy_events: has(Metric::PolicyEvents).then(|| self.policy_events().to_vec()),
+            active_policies: has(Metric::ActivePolicies).then(|| self.active_policies()),
+            by_age: has(Metric::ByAge).then(|| self.counter_by_age()),
+            by_strain: has(Metric::ByStrain).then(|| self.counter_by_strain()),
+        }
+    }
+
+    pub(crate) fn record_history(&mut self) {
+        if !self.config.history.is_empty() {
+            let entry = self.history_entry(&self.config.history);
+            self.history.push(entry);
+        }
+    }
+}
@@ -0,0 +1,358 @@
+use std::error::Error;
+use std::fmt;
+
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use crate::layers::{Generator, Layer};
+use crate::{AgeBand, Config, ConfigError, Graph, Simulation};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ImportError {
+    // A line of the nodes or of the edges of a layer that can't be used. The
+    // lines are counted from 1, including the header.
+    InvalidLine {
+        file: String,
+        line: usize,
+        message: String,
+    },
+    MissingColumn {
+        file: String,
+        column: String,
+    },
+    // A column of the nodes that is not a layer of the configuration.
+    UnknownColumn {
+        file: String,
+        column: String,
+    },
+    NoNodes,
+    DuplicateLayer(String),
+    // Edges for a layer that is not in the configuration.
+    UnknownLayer(String),
+    UnknownAgeBand(String),
+    InvalidConfig(Vec<ConfigError>),
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ImportError::InvalidLine {
+                file,
+                line,
+                message,
+            } => write!(f, "{}, line {}: {}", file, line, message),
+            ImportError::MissingColumn { file, column } => {
+                write!(f, "{}: missing column {}", file, column)
+            }
+            ImportError::UnknownColumn { file, column } => {
+                write!(f, "{}: column {} is not a layer", file, column)
+            }
+            ImportError::NoNodes => write!(f, "There are no nodes"),
+            ImportError::DuplicateLayer(layer) => {
+                write!(f, "The edges of {} are given more than once", layer)
+            }
+            ImportError::UnknownLayer(layer) => write!(f, "There is no layer called {}", layer),
+            ImportError::UnknownAgeBand(age) => write!(f, "No age band matches the age {}", age),
+            ImportError::InvalidConfig(errors) => {
+                let messages: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+                write!(f, "Invalid configuration: {}", messages.join("; "))
+            }
+        }
+    }
+}
+
+impl Error for ImportError {}
+
+// A text file with a header and a record per line, separated by tabs if the
+// header has any and by commas otherwise. Blank lines and lines starting
+// with `#` are skipped. Quoting is not supported.
+struct Records<'a> {
+    file: String,
+    header: Vec<&'a str>,
+    // The fields with the number of the line.
+    rows: Vec<(usize, Vec<&'a str>)>,
+}
+
+impl<'a> Records<'a> {
+    fn parse(file: &str, text: &'a str) -> Result<Self, ImportError> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .map(|(k, l)| (k + 1, l))
+            .filter(|(_, l)| !l.trim().is_empty() && !l.starts_with('#'));
+        let header = lines.next().map_or("", |(_, l)| l);
+        let delimiter = if header.contains('\t') { '\t' } else { ',' };
+        let split = |l: &'a str| -> Vec<&'a str> { l.split(delimiter).map(str::trim).collect() };
+        let header = split(header);
+        let mut rows = Vec::new();
+        for (k, line) in lines {
+            let fields = split(line);
+            if fields.len() != header.len() {
+                return Err(ImportError::InvalidLine {
+                    file: file.to_string(),
+                    line: k,
+                    message: format!("expected {} fields, found {}", header.len(), fields.len()),
+                });
+            }
+            rows.push((k, fields));
+        }
+        Ok(Records {
+            file: file.to_string(),
+            header,
+            rows,
+        })
+    }
+
+    fn column(&self, name: &str) -> Result<usize, ImportError> {
+        self.header
+            .iter()
+            .position(|c| *c == name)
+            .ok_or_else(|| ImportError::MissingColumn {
+                file: self.file.clone(),
+                column: name.to_string(),
+            })
+    }
+
+    fn error(&self, line: usize, message: String) -> ImportError {
+        ImportError::InvalidLine {
+            file: self.file.clone(),
+            line,
+            message,
+        }
+    }
+}
+
+// Dense indices for the ids of a column, in the order they first appear.
+#[derive(Default)]
+struct Ids(FxHashMap<String, usize>);
+
+impl Ids {
+    fn get(&mut self, id: &str) -> usize {
+        let n = self.0.len();
+        *self.0.entry(id.to_string()).or_insert(n)
+    }
+}
+
+struct EdgeList {
+    layer: String,
+    edges: Vec<(u32, u32)>,
+    weights: Option<Vec<f64>>,
+}
+
+// Agents and contacts read from files instead of generated. The nodes have
+// an `id`, a `household` and an `age`, which is the name of an age band or a
+// number of years within the range of a band named like `20-39` or `80+`.
+// A `state` column, as written by `Simulation::node_table`, is ignored: the
+// agents start susceptible. Any other column gives the group of each node in
+// the layer with the same name, e.g. `workplace`, and may be empty for the
+// nodes outside the layer. The edges of a layer have a `source` and a
+// `target` node id and optionally a `weight` multiplying the probability of
+// transmission through the link. Each link is given once, in either
+// direction.
+pub struct ImportedNetworks {
+    index: FxHashMap<String, usize>,
+    households: Vec<usize>,
+    ages: Vec<String>,
+    groups: Vec<(String, Vec<Option<usize>>)>,
+    layers: Vec<EdgeList>,
+}
+
+impl ImportedNetworks {
+    pub fn from_nodes(text: &str) -> Result<Self, ImportError> {
+        let records = Records::parse("nodes", text)?;
+        let id = records.column("id")?;
+        let household = records.column("household")?;
+        let age = records.column("age")?;
+        let state = records.column("state").ok();
+        let group_columns: Vec<usize> = (0..records.header.len())
+            .filter(|c| ![id, household, age].contains(c) && Some(*c) != state)
+            .collect();
+
+        let mut index = FxHashMap::default();
+        let mut household_ids = Ids::default();
+        let mut households = Vec::with_capacity(records.rows.len());
+        let mut ages = Vec::with_capacity(records.rows.len());
+        let mut group_ids: Vec<Ids> = group_columns.iter().map(|_| Ids::default()).collect();
+        let mut groups: Vec<(String, Vec<Option<usize>>)> = group_columns
+            .iter()
+            .map(|c| (records.header[*c].to_string(), Vec::new()))
+            .collect();
+        for (line, fields) in records.rows.iter() {
+            if index
+                .insert(fields[id].to_string(), households.len())
+                .is_some()
+            {
+                let message = format!("node {} appears more than once", fields[id]);
+                return Err(records.error(*line, message));
+            }
+            households.push(household_ids.get(fields[household]));
+            ages.push(fields[age].to_string());
+            for (k, c) in group_columns.iter().enumerate() {
+                let group = match fields[*c] {
+                    "" => None,
+                    g => Some(group_ids[k].get(g)),
+                };
+                groups[k].1.push(group);
+            }
+        }
+        if households.is_empty() {
+            return Err(ImportError::NoNodes);
+        }
+        Ok(ImportedNetworks {
+            index,
+            households,
+            ages,
+            groups,
+            layers: Vec::new(),
+        })
+    }
+
+    // Read the edges of a layer. Every node must be in the nodes.
+    pub fn add_layer(&mut self, layer: &str, text: &str) -> Result<(), ImportError> {
+        if self.layers.iter().any(|l| l.layer == layer) {
+            return Err(ImportError::DuplicateLayer(layer.to_string()));
+        }
+        let records = Records::parse(layer, text)?;
+        let source = records.column("source")?;
+        let target = records.column("target")?;
+        let weight = records.column("weight").ok();
+        let mut edges = Vec::with_capacity(records.rows.len());
+        let mut seen = FxHashSet::default();
+        let mut weights = Vec::new();
+        for (line, fields) in records.rows.iter() {
+            let node = |id: &str| {
+                self.index
+                    .get(id)
+                    .map(|i| *i as u32)
+                    .ok_or_else(|| records.error(*line, format!("unknown node {}", id)))
+            };
+            let (i, j) = (node(fields[source])?, node(fields[target])?);
+            if i == j {
+                let message = format!("node {} is linked to itself", fields[source]);
+                return Err(records.error(*line, message));
+            }
+            if !seen.insert((i.min(j), i.max(j))) {
+                let message = format!(
+                    "link between {} and {} appears more than once",
+                    fields[source], fields[target]
+                );
+                return Err(records.error(*line, message));
+            }
+            edges.push((i, j));
+            if let Some(w) = weight {
+                match fields[w].parse::<f64>() {
+                    Ok(w) if w.is_finite() && w >= 0. => weights.push(w),
+                    _ => {
+                        let message = format!("invalid weight {}", fields[w]);
+                        return Err(records.error(*line, message));
+                    }
+                }
+            }
+        }
+        self.layers.push(EdgeList {
+            layer: layer.to_string(),
+            edges,
+            weights: weight.map(|_| weights),
+        });
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.households.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.households.is_empty()
+    }
+
+    fn groups(&self, layer: &str) -> Option<&[Option<usize>]> {
+        self.groups
+            .iter()
+            .find(|(name, _)| name == layer)
+            .map(|(_, g)| g.as_slice())
+    }
+}
+
+// The index of the band called `age` or, if `age` is a number of years, of
+// the first band whose name is a range that contains it.
+fn age_band(bands: &[AgeBand], age: &str) -> Option<usize> {
+    if let Some(k) = bands.iter().position(|b| b.name == age) {
+        return Some(k);
+    }
+    let years: u32 = age.parse().ok()?;
+    bands.iter().position(|b| {
+        let range: Option<(u32, u32)> = match b.name.strip_suffix('+') {
+            Some(low) => low.parse().ok().map(|low| (low, u32::MAX)),
+            None => b.name.split_once('-').and_then(|(low, high)| {
+                Some((low.trim().parse().ok()?, high.trim().parse().ok()?))
+            }),
+        };
+        range.is_some_and(|(low, high)| low <= years && years <= high)
+    })
+}
+
+impl Simulation {
+    // Start a simulation with the imported agents, and contacts for the
+    // layers that have edges. The other layers are generated as usual, but
+    // the agents of a `groups` layer are put in the groups of the column with
+    // its name if there is one. The population is the number of nodes.
+    pub fn from_networks(
+        mut config: Config,
+        networks: &ImportedNetworks,
+    ) -> Result<Simulation, ImportError> {
+        config.total_population = networks.len();
+        let errors = config.validate();
+        if !errors.is_empty() {
+            return Err(ImportError::InvalidConfig(errors));
+        }
+        let specs = config.layers();
+        if let Some(l) = networks
+            .layers
+            .iter()
+            .find(|l| !specs.iter().any(|s| s.name == l.layer))
+        {
+            return Err(ImportError::UnknownLayer(l.layer.clone()));
+        }
+        if let Some((column, _)) = networks
+            .groups
+            .iter()
+            .find(|(name, _)| !specs.iter().any(|s| s.name == *name))
+        {
+            return Err(ImportError::UnknownColumn {
+                file: "nodes".to_string(),
+                column: column.clone(),
+            });
+        }
+        let ages = networks
+            .ages
+            .iter()
+            .map(|a| {
+                age_band(&config.age_bands, a).ok_or_else(|| ImportError::UnknownAgeBand(a.clone()))
+            })
+            .collect::<Result<Vec<usize>, _>>()?;
+
+        let n = networks.len();
+        let households = &networks.households;
+        let mut rng = config.rng();
+        let layers = specs
+            .into_iter()
+            .map(|spec| {
+                let edges = networks.layers.iter().find(|l| l.layer == spec.name);
+                let groups = match spec.generator {
+                    Generator::Household => Some(households.iter().map(|h| Some(*h)).collect()),
+                    _ => networks.groups(&spec.name).map(|g| g.to_vec()),
+                };
+                match edges {
+                    Some(edges) => {
+                        let graph = Graph::from_edges(n, &edges.edges, edges.weights.as_deref());
+                        Layer::imported(spec, graph, groups.unwrap_or_default())
+                    }
+                    None => {
+                        let assigned = networks.groups(&spec.name);
+                        Layer::new(spec, &config, households, &ages, assigned, &mut rng)
+                    }
+                }
+            })
+            .collect();
+        Ok(Simulation::with_layers(config, rng, ages, layers))
+    }
+}
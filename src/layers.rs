@@ -0,0 +1,676 @@
+use rand::distributions::Distribution;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use rand_distr::Binomial;
+use serde::{Deserialize, Serialize};
+use smallvec::SmallVec;
+use wasm_bindgen::prelude::*;
+
+use crate::validate::{Reason, Validator};
+use crate::{Config, ConfigError, Graph, GraphBuilder, Policy, RemoveItem, Simulation};
+
+// How the contacts of a layer are generated.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Generator {
+    // The members of each household are all connected.
+    Household,
+    // The agents are assigned at random to groups of `average_size` on
+    // average, like workplaces, and two agents of the same group are
+    // connected with probability `connectivity`.
+    Groups { average_size: f64, connectivity: f64 },
+    // Classrooms of `classroom_size` agents, with the agents of the same age
+    // band together as far as possible, dealt to schools of
+    // `classrooms_per_school` so that each school has classrooms of every
+    // band. Classmates are connected with probability
+    // `classroom_connectivity` and agents of different classrooms of the
+    // same school with probability `school_connectivity`.
+    Classrooms {
+        classroom_size: usize,
+        classrooms_per_school: usize,
+        classroom_connectivity: f64,
+        school_connectivity: f64,
+    },
+    // Every pair of agents is connected with the same probability.
+    Random { average_connections: f64 },
+}
+
+// A network through which the disease spreads.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ContactLayer {
+    pub name: String,
+    #[serde(flatten)]
+    pub generator: Generator,
+    pub undetected_coef: f64,
+    pub detected_coef: f64,
+    // The contacts in the layers with a lower value are traced first.
+    pub tracing_priority: usize,
+    // The age bands of the agents in the layer, or all of them if missing.
+    #[serde(default)]
+    pub age_bands: Option<Vec<String>>,
+}
+
+// A contact layer of a simulation, with the changes made by the policies.
+#[derive(Serialize, Deserialize, Clone)]
+pub(crate) struct Layer {
+    pub(crate) spec: ContactLayer,
+    pub(crate) graph: Graph,
+    // The group of each agent that is closed as a whole: its household,
+    // workplace or school.
+    pub(crate) groups: Vec<Option<usize>>,
+    pub(crate) ngroups: usize,
+    undetected_coef_mod: SmallVec<[f64; 4]>,
+    detected_coef_mod: SmallVec<[f64; 4]>,
+    // The groups with an index below the largest value are closed.
+    closures_mod: SmallVec<[usize; 4]>,
+    connections_reduction_mod: SmallVec<[f64; 4]>,
+    // The full graph of an imported layer, of which `graph` keeps the links
+    // that are not cut.
+    imported: Option<Graph>,
+}
+
+impl Layer {
+    // Generate the contacts of a layer. The agents of a `Groups` layer are
+    // put in the `assigned` groups when given, and at random otherwise.
+    pub(crate) fn new<R: Rng>(
+        spec: ContactLayer,
+        config: &Config,
+        households: &[usize],
+        ages: &[usize],
+        assigned: Option<&[Option<usize>]>,
+        rng: &mut R,
+    ) -> Layer {
+        let members = members(config, &spec, ages);
+        let (graph, groups, ngroups) = match spec.generator {
+            Generator::Household => household_graph(households, &members),
+            Generator::Groups {
+                average_size,
+                connectivity,
+            } => match assigned {
+                Some(assigned) => assigned_groups_graph(assigned, connectivity, rng),
+                None => groups_graph(&members, average_size, connectivity, rng),
+            },
+            Generator::Classrooms {
+                classroom_size,
+                classrooms_per_school,
+                classroom_connectivity,
+                school_connectivity,
+            } => classrooms_graph(
+                &members,
+                ages,
+                classroom_size,
+                classrooms_per_school,
+                [classroom_connectivity, school_connectivity],
+                rng,
+            ),
+            Generator::Random {
+                average_connections,
+            } => (
+                random_graph(&members, average_connections, rng),
+                Vec::new(),
+                0,
+            ),
+        };
+        Layer::with_graph(spec, graph, groups, ngroups, None)
+    }
+
+    // A layer with the given contacts instead of generated ones.
+    pub(crate) fn imported(spec: ContactLayer, graph: Graph, groups: Vec<Option<usize>>) -> Layer {
+        let ngroups = groups.iter().flatten().max().map_or(0, |g| g + 1);
+        Layer::with_graph(spec, graph.clone(), groups, ngroups, Some(graph))
+    }
+
+    fn with_graph(
+        spec: ContactLayer,
+        graph: Graph,
+        groups: Vec<Option<usize>>,
+        ngroups: usize,
+        imported: Option<Graph>,
+    ) -> Layer {
+        Layer {
+            spec,
+            graph,
+            groups,
+            ngroups,
+            undetected_coef_mod: Default::default(),
+            detected_coef_mod: Default::default(),
+            closures_mod: Default::default(),
+            connections_reduction_mod: Default::default(),
+            imported,
+        }
+    }
+
+    // Whether each agent belongs to the layer: whether it is in one of the
+    // groups for the layers with groups and otherwise whether it is in one of
+    // the age bands of the layer. Every agent belongs to the imported layers
+    // without groups.
+    pub(crate) fn members(&self, config: &Config, ages: &[usize]) -> Vec<bool> {
+        if !self.groups.is_empty() {
+            self.groups.iter().map(|g| g.is_some()).collect()
+        } else if self.imported.is_some() {
+            vec![true; ages.len()]
+        } else {
+            members(config, &self.spec, ages)
+        }
+    }
+
+    pub(crate) fn undetected_coef(&self) -> f64 {
+        self.spec.undetected_coef * self.undetected_coef_mod.iter().product::<f64>()
+    }
+
+    pub(crate) fn detected_coef(&self) -> f64 {
+        self.spec.detected_coef * self.detected_coef_mod.iter().product::<f64>()
+    }
+
+    // Whether the group of agent `i` is open.
+    pub(crate) fn is_open(&self, i: usize) -> bool {
+        match (self.closures_mod.iter().max(), self.groups.get(i).copied().flatten()) {
+            (Some(val), Some(group)) => group >= *val,
+            _ => true,
+        }
+    }
+
+    fn connections_reduction(&self) -> f64 {
+        self.connections_reduction_mod
+            .iter()
+            .fold(0., |a, &b| f64::max(a, b))
+    }
+}
+
+impl Config {
+    // The household, workplace, school and world layers described by the
+    // fields of the configuration, followed by `contact_layers`.
+    pub fn layers(&self) -> Vec<ContactLayer> {
+        let bands = |school: bool| {
+            Some(
+                self.age_bands
+                    .iter()
+                    .filter(|b| b.school == school)
+                    .map(|b| b.name.clone())
+                    .collect(),
+            )
+        };
+        let mut res = vec![
+            ContactLayer {
+                name: "family".to_string(),
+                generator: Generator::Household,
+                undetected_coef: self.family_contact_undetected_coef,
+                detected_coef: self.family_contact_detected_coef,
+                tracing_priority: 0,
+                age_bands: None,
+            },
+            ContactLayer {
+                name: "workplace".to_string(),
+                generator: Generator::Groups {
+                    average_size: self.average_workplace_size,
+                    connectivity: self.workplace_connectivity,
+                },
+                undetected_coef: self.workplace_contact_undetected_coef,
+                detected_coef: self.workplace_contact_detected_coef,
+                tracing_priority: 1,
+                age_bands: bands(false),
+            },
+            ContactLayer {
+                name: "school".to_string(),
+                generator: Generator::Classrooms {
+                    classroom_size: self.classroom_size,
+                    classrooms_per_school: self.classrooms_per_school,
+                    classroom_connectivity: self.classroom_connectivity,
+                    school_connectivity: self.school_connectivity,
+                },
+                undetected_coef: self.school_contact_undetected_coef,
+                detected_coef: self.school_contact_detected_coef,
+                tracing_priority: 1,
+                age_bands: bands(true),
+            },
+            ContactLayer {
+                name: "world".to_string(),
+                generator: Generator::Random {
+                    average_connections: self.average_world_connections,
+                },
+                undetected_coef: self.world_contact_undetected_coef,
+                detected_coef: self.world_contact_detected_coef,
+                tracing_priority: 2,
+                age_bands: None,
+            },
+        ];
+        res.extend(self.contact_layers.iter().cloned());
+        res
+    }
+
+    // The layers named by the policies must exist.
+    pub fn validate_policies(&self, policies: &[Policy]) -> Vec<ConfigError> {
+        let layers = self.layers();
+        let mut v = Validator::default();
+        for name in policies.iter().filter_map(|p| p.kind.layer()) {
+            if !layers.iter().any(|l| l.name == name) {
+                let name = name.to_string();
+                v.push("policies.layer", Reason::UnknownLayer { name });
+            }
+        }
+        v.finish()
+    }
+
+    pub(crate) fn validate_layers(&self, v: &mut Validator) {
+        let layers = self.layers();
+        for (k, layer) in layers.iter().enumerate().skip(4) {
+            if layer.name.is_empty() {
+                v.push("contact_layers.name", Reason::Empty);
+            } else if layers[..k].iter().any(|l| l.name == layer.name) {
+                v.push(
+                    "contact_layers.name",
+                    Reason::DuplicateName {
+                        name: layer.name.clone(),
+                    },
+                );
+            }
+            match layer.generator {
+                Generator::Household => {}
+                Generator::Groups {
+                    average_size,
+                    connectivity,
+                } => {
+                    v.positive("contact_layers.average_size", average_size);
+                    v.probability("contact_layers.connectivity", connectivity);
+                }
+                Generator::Classrooms {
+                    classroom_size,
+                    classrooms_per_school,
+                    classroom_connectivity,
+                    school_connectivity,
+                } => {
+                    for (field, value) in [
+                        ("contact_layers.classroom_size", classroom_size),
+                        ("contact_layers.classrooms_per_school", classrooms_per_school),
+                    ]
+                    .iter()
+                    {
+                        if *value == 0 {
+                            v.push(field, Reason::NotPositive { value: 0. });
+                        }
+                    }
+                    v.probability(
+                        "contact_layers.classroom_connectivity",
+                        classroom_connectivity,
+                    );
+                    v.probability("contact_layers.school_connectivity", school_connectivity);
+                }
+                Generator::Random {
+                    average_connections,
+                } => {
+                    v.non_negative("contact_layers.average_connections", average_connections);
+                }
+            }
+            v.non_negative("contact_layers.undetected_coef", layer.undetected_coef);
+            v.non_negative("contact_layers.detected_coef", layer.detected_coef);
+            for band in layer.age_bands.iter().flatten() {
+                if !self.age_bands.iter().any(|b| b.name == *band) {
+                    v.push(
+                        "contact_layers.age_bands",
+                        Reason::UnknownAgeBand { name: band.clone() },
+                    );
+                }
+            }
+        }
+    }
+}
+
+// Whether each agent belongs to the layer.
+fn members(config: &Config, spec: &ContactLayer, ages: &[usize]) -> Vec<bool> {
+    match &spec.age_bands {
+        Some(bands) => ages
+            .iter()
+            .map(|a| bands.contains(&config.age_bands[*a].name))
+            .collect(),
+        None => vec![true; ages.len()],
+    }
+}
+
+fn household_graph(households: &[usize], members: &[bool]) -> (Graph, Vec<Option<usize>>, usize) {
+    let groups: Vec<Option<usize>> = households
+        .iter()
+        .zip(members.iter())
+        .map(|(h, m)| if *m { Some(*h) } else { None })
+        .collect();
+    let ngroups = households.iter().max().map_or(0, |h| h + 1);
+    let mut g = GraphBuilder::with_capacity(households.len());
+    let mut group_members: Vec<Vec<usize>> = vec![Vec::new(); ngroups];
+    for (i, group) in groups.iter().enumerate() {
+        g.register_node();
+        if let Some(h) = group {
+            for j in group_members[*h].iter() {
+                g.add_link(*j, i);
+            }
+            group_members[*h].push(i);
+        }
+    }
+    (g.build(), groups, ngroups)
+}
+
+// Link `i` to each of `nodes` with probability `p`.
+fn link_some<R: Rng>(g: &mut GraphBuilder, i: usize, nodes: &[usize], p: f64, rng: &mut R) {
+    let nconnections = Binomial::new(nodes.len() as u64, p)
+        .unwrap()
+        .sample(rng) as usize;
+    for c in rand::seq::index::sample(rng, nodes.len(), nconnections).iter() {
+        g.add_link(nodes[c], i);
+    }
+}
+
+fn groups_graph<R: Rng>(
+    members: &[bool],
+    average_size: f64,
+    connectivity: f64,
+    rng: &mut R,
+) -> (Graph, Vec<Option<usize>>, usize) {
+    let n = members.len();
+    let nmembers = members.iter().filter(|m| **m).count();
+    let ngroups = f64::max(nmembers as f64 / average_size, 1.) as usize;
+    let mut g = GraphBuilder::with_capacity(n);
+    let mut group_members: Vec<Vec<usize>> = vec![Vec::new(); ngroups];
+    let mut groups = Vec::with_capacity(n);
+    for (i, member) in members.iter().enumerate() {
+        g.register_node();
+        if !member {
+            groups.push(None);
+            continue;
+        }
+        let group = rng.gen_range(0, ngroups);
+        link_some(&mut g, i, &group_members[group], connectivity, rng);
+        group_members[group].push(i);
+        groups.push(Some(group));
+    }
+    (g.build(), groups, ngroups)
+}
+
+// Like `groups_graph` but with the group of each agent given.
+fn assigned_groups_graph<R: Rng>(
+    assigned: &[Option<usize>],
+    connectivity: f64,
+    rng: &mut R,
+) -> (Graph, Vec<Option<usize>>, usize) {
+    let ngroups = assigned.iter().flatten().max().map_or(0, |g| g + 1);
+    let mut g = GraphBuilder::with_capacity(assigned.len());
+    let mut group_members: Vec<Vec<usize>> = vec![Vec::new(); ngroups];
+    for (i, group) in assigned.iter().enumerate() {
+        g.register_node();
+        if let Some(group) = group {
+            link_some(&mut g, i, &group_members[*group], connectivity, rng);
+            group_members[*group].push(i);
+        }
+    }
+    (g.build(), assigned.to_vec(), ngroups)
+}
+
+// The groups are the schools. The classroom `c` is in the school
+// `c % nschools`.
+fn classrooms_graph<R: Rng>(
+    members: &[bool],
+    ages: &[usize],
+    classroom_size: usize,
+    classrooms_per_school: usize,
+    connectivity: [f64; 2],
+    rng: &mut R,
+) -> (Graph, Vec<Option<usize>>, usize) {
+    let n = members.len();
+    let mut pupils: Vec<usize> = (0..n).filter(|i| members[*i]).collect();
+    pupils.shuffle(rng);
+    // The sort is stable, so the pupils of each band stay shuffled.
+    pupils.sort_by_key(|i| ages[*i]);
+    let mut classrooms = vec![None; n];
+    for (k, chunk) in pupils.chunks(classroom_size).enumerate() {
+        for i in chunk.iter() {
+            classrooms[*i] = Some(k);
+        }
+    }
+    let nclassrooms = pupils.len().div_ceil(classroom_size);
+    let nschools = nclassrooms.div_ceil(classrooms_per_school);
+
+    let mut g = GraphBuilder::with_capacity(n);
+    let mut classmates: Vec<Vec<usize>> = vec![Vec::new(); nclassrooms];
+    let mut schoolmates: Vec<Vec<usize>> = vec![Vec::new(); nschools];
+    for i in 0..n {
+        g.register_node();
+        let classroom = match classrooms[i] {
+            Some(c) => c,
+            None => continue,
+        };
+        let school = classroom % nschools;
+        let others: Vec<usize> = schoolmates[school]
+            .iter()
+            .copied()
+            .filter(|j| classrooms[*j] != Some(classroom))
+            .collect();
+        link_some(&mut g, i, &classmates[classroom], connectivity[0], rng);
+        link_some(&mut g, i, &others, connectivity[1], rng);
+        classmates[classroom].push(i);
+        schoolmates[school].push(i);
+    }
+    let schools = classrooms.iter().map(|c| c.map(|c| c % nschools)).collect();
+    (g.build(), schools, nschools)
+}
+
+fn random_graph<R: Rng>(members: &[bool], average_connections: f64, rng: &mut R) -> Graph {
+    let n = members.len();
+    let nmembers = members.iter().filter(|m| **m).count();
+    let p = f64::min(average_connections / nmembers as f64, 1.);
+    let mut g = GraphBuilder::with_capacity(n);
+    let mut earlier = Vec::with_capacity(nmembers);
+    for (i, member) in members.iter().enumerate() {
+        g.register_node();
+        if *member {
+            link_some(&mut g, i, &earlier, p, rng);
+            earlier.push(i);
+        }
+    }
+    g.build()
+}
+
+impl Simulation {
+    pub(crate) fn layer_index(&self, name: &str) -> Option<usize> {
+        self.layers.iter().position(|l| l.spec.name == name)
+    }
+
+    fn layer_mut(&mut self, name: &str) -> Option<&mut Layer> {
+        self.layers.iter_mut().find(|l| l.spec.name == name)
+    }
+
+    // Whether agent `i` has contacts in the layer `k`, i.e. whether its
+    // group is open.
+    pub(crate) fn uses_layer(&self, k: usize, i: usize) -> bool {
+        self.layers[k].is_open(i)
+    }
+
+    // The names of the layers, in the order of the network counters.
+    pub fn layer_names(&self) -> Vec<String> {
+        self.layers.iter().map(|l| l.spec.name.clone()).collect()
+    }
+
+    // Build the graph of a random layer again, with the current reduction of
+    // its connections. Imported layers keep each of their links with the
+    // probability of not being cut instead.
+    fn rebuild_random_layer(&mut self, k: usize) {
+        let layer = &self.layers[k];
+        if let Some(imported) = &layer.imported {
+            let keep = 1. - layer.connections_reduction();
+            let (edges, weights) = imported.edges();
+            let mut kept_edges = Vec::new();
+            let mut kept_weights = Vec::new();
+            for (e, edge) in edges.iter().enumerate() {
+                if self.rng.gen::<f64>() < keep {
+                    kept_edges.push(*edge);
+                    kept_weights.extend(weights.as_ref().map(|w| w[e]));
+                }
+            }
+            let n = self.states.len();
+            let weights = weights.map(|_| kept_weights);
+            self.layers[k].graph = Graph::from_edges(n, &kept_edges, weights.as_deref());
+            return;
+        }
+        let average_connections = match layer.spec.generator {
+            Generator::Random {
+                average_connections,
+            } => average_connections,
+            _ => return,
+        };
+        let members = members(&self.config, &layer.spec, &self.ages);
+        let reduction = layer.connections_reduction();
+        let graph = random_graph(
+            &members,
+            (1. - reduction) * average_connections,
+            &mut self.rng,
+        );
+        self.layers[k].graph = graph;
+    }
+}
+
+// Policies address the layers by name. The names are checked when the
+// policies are added, and these calls ignore the unknown ones.
+#[wasm_bindgen]
+impl Simulation {
+    // Close the given fraction of the groups of a layer, e.g. of the
+    // workplaces or of the schools.
+    pub fn close_fraction_of_groups(&mut self, layer: &str, fraction: f64) {
+        if let Some(layer) = self.layer_mut(layer) {
+            let index = (fraction * layer.ngroups as f64) as usize;
+            layer.closures_mod.push(index);
+        }
+    }
+
+    pub fn undo_close_fraction_of_groups(&mut self, layer: &str, fraction: f64) -> bool {
+        match self.layer_mut(layer) {
+            Some(layer) => {
+                let index = (fraction * layer.ngroups as f64) as usize;
+                layer.closures_mod.remove_item(&index)
+            }
+            None => false,
+        }
+    }
+
+    pub fn multiply_undetected_infectability(&mut self, layer: &str, coef: f64) {
+        if let Some(layer) = self.layer_mut(layer) {
+            layer.undetected_coef_mod.push(coef);
+        }
+    }
+
+    pub fn multiply_detected_infectability(&mut self, layer: &str, coef: f64) {
+        if let Some(layer) = self.layer_mut(layer) {
+            layer.detected_coef_mod.push(coef);
+        }
+    }
+
+    pub fn undo_multiply_undetected_infectability(&mut self, layer: &str, coef: f64) -> bool {
+        self.layer_mut(layer)
+            .is_some_and(|l| l.undetected_coef_mod.remove_item(&coef))
+    }
+
+    pub fn undo_multiply_detected_infectability(&mut self, layer: &str, coef: f64) -> bool {
+        self.layer_mut(layer)
+            .is_some_and(|l| l.detected_coef_mod.remove_item(&coef))
+    }
+
+    // Only for random and imported layers. The graph is built anew whenever
+    // the largest reduction changes.
+    pub fn disable_fraction_of_connections(&mut self, layer: &str, frac: f64) {
+        let k = match self.layer_index(layer) {
+            Some(k) => k,
+            None => return,
+        };
+        let currmax = self.layers[k].connections_reduction();
+        self.layers[k].connections_reduction_mod.push(frac);
+        if frac > currmax {
+            self.rebuild_random_layer(k);
+        }
+    }
+
+    pub fn undo_disable_fraction_of_connections(&mut self, layer: &str, frac: f64) -> bool {
+        let k = match self.layer_index(layer) {
+            Some(k) => k,
+            None => return false,
+        };
+        if !self.layers[k].connections_reduction_mod.remove_item(&frac) {
+            return false;
+        }
+        if self.layers[k].connections_reduction() < frac {
+            self.rebuild_random_layer(k);
+        }
+        true
+    }
+}
+
+// The methods for the fixed layers from before the layers had names, kept
+// for the callers of the web interface.
+#[wasm_bindgen]
+impl Simulation {
+    pub fn disable_fraction_of_workplaces(&mut self, fraction: f64) {
+        self.close_fraction_of_groups("workplace", fraction);
+    }
+
+    pub fn undo_disable_fraction_of_workplaces(&mut self, fraction: f64) -> bool {
+        self.undo_close_fraction_of_groups("workplace", fraction)
+    }
+
+    pub fn disable_fraction_of_schools(&mut self, fraction: f64) {
+        self.close_fraction_of_groups("school", fraction);
+    }
+
+    pub fn undo_disable_fraction_of_schools(&mut self, fraction: f64) -> bool {
+        self.undo_close_fraction_of_groups("school", fraction)
+    }
+
+    pub fn multiply_undetected_household_infectability(&mut self, coef: f64) {
+        self.multiply_undetected_infectability("family", coef);
+    }
+
+    pub fn multiply_detected_household_infectability(&mut self, coef: f64) {
+        self.multiply_detected_infectability("family", coef);
+    }
+
+    pub fn multiply_undetected_workplace_infectability(&mut self, coef: f64) {
+        self.multiply_undetected_infectability("workplace", coef);
+    }
+
+    pub fn multiply_detected_workplace_infectability(&mut self, coef: f64) {
+        self.multiply_detected_infectability("workplace", coef);
+    }
+
+    pub fn multiply_undetected_world_infectability(&mut self, coef: f64) {
+        self.multiply_undetected_infectability("world", coef);
+    }
+
+    pub fn multiply_detected_world_infectability(&mut self, coef: f64) {
+        self.multiply_detected_infectability("world", coef);
+    }
+
+    pub fn undo_multiply_undetected_household_infectability(&mut self, coef: f64) -> bool {
+        self.undo_multiply_undetected_infectability("family", coef)
+    }
+
+    pub fn undo_multiply_detected_household_infectability(&mut self, coef: f64) -> bool {
+        self.undo_multiply_detected_infectability("family", coef)
+    }
+
+    pub fn undo_multiply_undetected_workplace_infectability(&mut self, coef: f64) -> bool {
+        self.undo_multiply_undetected_infectability("workplace", coef)
+    }
+
+    pub fn undo_multiply_detected_workplace_infectability(&mut self, coef: f64) -> bool {
+        self.undo_multiply_detected_infectability("workplace", coef)
+    }
+
+    pub fn undo_multiply_undetected_world_infectability(&mut self, coef: f64) -> bool {
+        self.undo_multiply_undetected_infectability("world", coef)
+    }
+
+    pub fn undo_multiply_detected_world_infectability(&mut self, coef: f64) -> bool {
+        self.undo_multiply_detected_infectability("world", coef)
+    }
+
+    pub fn disable_fraction_of_world_connections(&mut self, frac: f64) {
+        self.disable_fraction_of_connections("world", frac);
+    }
+
+    pub fn undo_disable_fraction_of_world_connections(&mut self, frac: f64) -> bool {
+        self.undo_disable_fraction_of_connections("world", frac)
+    }
+}
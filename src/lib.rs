@@ -1,18 +1,63 @@
+mod age;
+mod branch;
+mod day;
+mod ensemble;
+mod export;
+mod graphml;
+mod history;
+mod import;
+mod layers;
+mod policy;
+mod snapshot;
+mod stats;
+mod strain;
+mod sweep;
+mod transmission;
 mod utils;
+mod vaccination;
+mod validate;
+
+use age::AgeSampler;
+pub use age::{AgeBand, AgeCounter};
+pub use branch::{Branch, BranchDiff, BranchOutcome, BranchReport, BranchSpec, Scenario};
+use day::{ActiveAgents, Day, Infection, NewStates};
+pub use ensemble::{
+    run_ensemble, run_ensemble_js, Band, DaySummary, EnsembleSpec, EnsembleSummary,
+    DEFAULT_QUANTILES,
+};
+pub use export::{Column, ColumnData, Table};
+pub use history::{HistoryEntry, Metric};
+pub use import::{ImportError, ImportedNetworks};
+use layers::Layer;
+pub use layers::{ContactLayer, Generator};
+use policy::PolicyEngine;
+pub use policy::{
+    Operator, Policy, PolicyEvent, PolicyEventKind, PolicyKind, Shutdown, Trigger, Variable,
+};
+pub use snapshot::SnapshotError;
+pub use stats::LayerStats;
+pub use strain::{Strain, StrainCounter, ORIGINAL_STRAIN};
+pub use sweep::{
+    run_sweep, Outcome, Parameter, Sampling, SweepError, SweepRow, SweepSpec, Values,
+};
+pub use transmission::{InfectionEvent, TransmissionTree};
+pub use vaccination::Priority;
+use vaccination::Vaccination;
+use validate::ErrorMessage;
+pub use validate::{ConfigError, Reason};
 
-use std::collections::hash_set;
 use std::collections::VecDeque;
-use std::iter;
+use std::hash::BuildHasherDefault;
 
+use gloo_utils::format::JsValueSerdeExt;
 use wasm_bindgen::prelude::*;
-use web_sys;
 
-use rand;
 use rand::distributions::weighted::alias_method::WeightedIndex;
 use rand::distributions::Distribution;
-use rand::Rng;
-use rand_distr::Binomial;
-use rustc_hash::FxHashSet;
+use rand::{Rng, SeedableRng};
+use rand_pcg::Pcg64;
+use indexmap::IndexSet;
+use rustc_hash::FxHasher;
 use serde::{Deserialize, Serialize};
 use smallvec::SmallVec;
 
@@ -31,11 +76,21 @@ const SUSCEPTIBLE_INFECTED_PROFILE: [f64; 18] = [
     0.0075, 0.0035, 0.0025, 0.00075,
 ];
 
+// No latent period by default: new infections are immediately subject to the
+// infectability profile, which already starts at zero.
+const EXPOSED_INFECTED_PROFILE: [f64; 0] = [];
+
 const INFECTED_DETECTED_PROFILE: [f64; 18] = [
     0., 0., 0., 0.01, 0.02, 0.03, 0.05, 0.07, 0.10, 0.10, 0.15, 0.10, 0.10, 0.10, 0.07, 0.05, 0.02,
     0.01,
 ];
 
+// Asymptomatic agents are much less likely to suspect they are infected.
+const ASYMPTOMATIC_DETECTED_PROFILE: [f64; 18] = [
+    0., 0., 0., 0.001, 0.002, 0.003, 0.005, 0.007, 0.01, 0.01, 0.015, 0.01, 0.01, 0.01, 0.007,
+    0.005, 0.002, 0.001,
+];
+
 const INFECTED_CRITICAL_PROFILE: [f64; 14] = [
     0., 0., 0., 0., 0., 0., 0., 0., 0.01, 0.02, 0.05, 0.03, 0.02, 0.01,
 ];
@@ -53,15 +108,30 @@ const INMUNE_SUSCEPTIBLE_PROFILE: [f64; 30] = [
     0., 0., 0., 0., 0., 0.00189723,
 ];
 
+const VACCINATED_SUSCEPTIBLE_PROFILE: [f64; 1] = [0.001];
+
 const FAMILY_CONTACT_INFECTED_COEF: f64 = 1.;
 const FAMILY_CONTACT_DETECTED_COEF: f64 = 0.3;
 
 const WORKPLACE_CONTACT_INFECTED_COEF: f64 = 0.7;
 const WORKPLACE_CONTACT_DETECTED_COEF: f64 = 0.05;
 
+const SCHOOL_CONTACT_INFECTED_COEF: f64 = 0.5;
+const SCHOOL_CONTACT_DETECTED_COEF: f64 = 0.05;
+
 const WORLD_CONTACT_INFECTED_COEF: f64 = 0.15;
 const WORLD_CONTACT_DETECTED_COEF: f64 = 0.01;
 
+// Every infection is symptomatic by default.
+const DEFAULT_ASYMPTOMATIC_FRACTION: f64 = 0.;
+const DEFAULT_ASYMPTOMATIC_INFECTIOUSNESS: f64 = 0.5;
+
+const DEFAULT_VACCINE_EFFICACY_INFECTION: f64 = 0.7;
+const DEFAULT_VACCINE_EFFICACY_SEVERE: f64 = 0.9;
+const DEFAULT_VACCINE_DOSES: usize = 2;
+const DEFAULT_VACCINE_DOSE_INTERVAL: usize = 21;
+const DEFAULT_VACCINE_FIRST_DOSE_PROTECTION: f64 = 0.5;
+
 const DEFAULT_INITIAL_OUTBREAK_SIZE: usize = 20;
 
 const DEFAULT_TOTAL_POPULATION: usize = 300000;
@@ -70,21 +140,57 @@ const DEFAULT_AVERAGE_WORKPLACE_SIZE: f64 = 15.;
 
 const DEFAULT_WORKPLACE_CONNECTIVITY: f64 = 0.8;
 
+const DEFAULT_CLASSROOM_SIZE: usize = 25;
+const DEFAULT_CLASSROOMS_PER_SCHOOL: usize = 12;
+const DEFAULT_CLASSROOM_CONNECTIVITY: f64 = 0.8;
+const DEFAULT_SCHOOL_CONNECTIVITY: f64 = 0.02;
+
 const DEFAULT_WORLD_CONNECTIONS: f64 = 50.;
 
 fn sat_index<T: Copy>(v: &[T], i: usize) -> T {
     *v.get(i).unwrap_or_else(|| v.last().unwrap())
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
 pub struct Config {
     susceptible_infected_profile: Vec<f64>,
+    exposed_infected_profile: Vec<f64>,
     infected_detected_profile: Vec<f64>,
+    asymptomatic_detected_profile: Vec<f64>,
     infected_severe_profile: Vec<f64>,
     infected_immune_profile: Vec<f64>,
     severe_immune_profile: Vec<f64>,
     severe_dead_profile: Vec<f64>,
     immune_susceptible_profile: Vec<f64>,
+    // Age bands of the population. The per age lists below are either empty,
+    // to use the same value for every band, or have one entry per band.
+    age_bands: Vec<AgeBand>,
+    infected_severe_profile_by_age: Vec<Vec<f64>>,
+    severe_dead_profile_by_age: Vec<Vec<f64>>,
+    // Relative probability of getting infected on each contact.
+    susceptibility_by_age: Vec<f64>,
+    // Fraction of the new infections that never become Severe.
+    asymptomatic_fraction: f64,
+    // Infectiousness of Asymptomatic agents relative to Infected (Undetected).
+    asymptomatic_infectiousness: f64,
+    // Relative reduction of the probability of getting infected and of
+    // worsening once infected.
+    vaccine_efficacy_infection: f64,
+    vaccine_efficacy_severe: f64,
+    // Either 1 or 2.
+    vaccine_doses: usize,
+    // Days between the first and the second dose.
+    vaccine_dose_interval: usize,
+    // Fraction of the efficacy given by the first of two doses.
+    vaccine_first_dose_protection: f64,
+    vaccinated_susceptible_profile: Vec<f64>,
+    // Strains other than the original one.
+    strains: Vec<Strain>,
+    // Protection against infection by the strain of the column given by a
+    // previous infection by the strain of the row, with the original strain
+    // first. Empty means full protection against every strain.
+    cross_immunity: Vec<Vec<f64>>,
     initial_outbreak_size: usize,
     total_population: usize,
     hospital_capacity: usize,
@@ -96,21 +202,60 @@ pub struct Config {
     workplace_connectivity: f64,
     workplace_contact_undetected_coef: f64,
     workplace_contact_detected_coef: f64,
+    // The agents of the age bands marked as `school` go to a classroom
+    // instead of to a workplace. Classrooms hold `classroom_size` pupils and
+    // schools `classrooms_per_school` classrooms.
+    classroom_size: usize,
+    classrooms_per_school: usize,
+    // Probability of two classmates being connected, and of two pupils of
+    // different classrooms of the same school.
+    classroom_connectivity: f64,
+    school_connectivity: f64,
+    school_contact_undetected_coef: f64,
+    school_contact_detected_coef: f64,
     average_world_connections: f64,
     world_contact_undetected_coef: f64,
     world_contact_detected_coef: f64,
+    // Contact layers other than the household, workplace, school and world
+    // ones described by the fields above.
+    contact_layers: Vec<ContactLayer>,
+    // Only step the agents that can change and let the infectious ones infect
+    // their contacts, instead of checking the contacts of every Susceptible
+    // agent. Statistically equivalent and much faster when there are few
+    // infections, but the trajectories for a given seed are different.
+    event_driven: bool,
+    // Quantities stored every day in the history of the simulation. Empty to
+    // keep no history.
+    history: Vec<Metric>,
+    seed: Option<u64>,
 }
 
 impl Default for Config {
     fn default() -> Config {
         Config {
             susceptible_infected_profile: SUSCEPTIBLE_INFECTED_PROFILE.to_vec(),
+            exposed_infected_profile: EXPOSED_INFECTED_PROFILE.to_vec(),
             infected_detected_profile: INFECTED_DETECTED_PROFILE.to_vec(),
+            asymptomatic_detected_profile: ASYMPTOMATIC_DETECTED_PROFILE.to_vec(),
             infected_severe_profile: INFECTED_CRITICAL_PROFILE.to_vec(),
             infected_immune_profile: INFECTED_INMUNE_PROFILE.to_vec(),
             severe_immune_profile: CRITICAL_INMUNE_PROFILE.to_vec(),
             severe_dead_profile: CRITICAL_DEATH_PROFILE.to_vec(),
             immune_susceptible_profile: INMUNE_SUSCEPTIBLE_PROFILE.to_vec(),
+            age_bands: age::default_age_bands(),
+            infected_severe_profile_by_age: Vec::new(),
+            severe_dead_profile_by_age: Vec::new(),
+            susceptibility_by_age: Vec::new(),
+            asymptomatic_fraction: DEFAULT_ASYMPTOMATIC_FRACTION,
+            asymptomatic_infectiousness: DEFAULT_ASYMPTOMATIC_INFECTIOUSNESS,
+            vaccine_efficacy_infection: DEFAULT_VACCINE_EFFICACY_INFECTION,
+            vaccine_efficacy_severe: DEFAULT_VACCINE_EFFICACY_SEVERE,
+            vaccine_doses: DEFAULT_VACCINE_DOSES,
+            vaccine_dose_interval: DEFAULT_VACCINE_DOSE_INTERVAL,
+            vaccine_first_dose_protection: DEFAULT_VACCINE_FIRST_DOSE_PROTECTION,
+            vaccinated_susceptible_profile: VACCINATED_SUSCEPTIBLE_PROFILE.to_vec(),
+            strains: Vec::new(),
+            cross_immunity: Vec::new(),
             initial_outbreak_size: DEFAULT_INITIAL_OUTBREAK_SIZE,
             total_population: DEFAULT_TOTAL_POPULATION,
             hospital_capacity: DEFAULT_HOSPITAL_CAPACITY,
@@ -122,159 +267,299 @@ impl Default for Config {
             workplace_connectivity: DEFAULT_WORKPLACE_CONNECTIVITY,
             workplace_contact_undetected_coef: WORKPLACE_CONTACT_INFECTED_COEF,
             workplace_contact_detected_coef: WORKPLACE_CONTACT_DETECTED_COEF,
+            classroom_size: DEFAULT_CLASSROOM_SIZE,
+            classrooms_per_school: DEFAULT_CLASSROOMS_PER_SCHOOL,
+            classroom_connectivity: DEFAULT_CLASSROOM_CONNECTIVITY,
+            school_connectivity: DEFAULT_SCHOOL_CONNECTIVITY,
+            school_contact_undetected_coef: SCHOOL_CONTACT_INFECTED_COEF,
+            school_contact_detected_coef: SCHOOL_CONTACT_DETECTED_COEF,
             average_world_connections: DEFAULT_WORLD_CONNECTIONS,
             world_contact_undetected_coef: WORLD_CONTACT_INFECTED_COEF,
             world_contact_detected_coef: WORLD_CONTACT_DETECTED_COEF,
+            contact_layers: Vec::new(),
+            event_driven: false,
+            history: history::default_metrics(),
+            seed: None,
         }
     }
 }
 
-impl Config {
-    fn nworkplaces(&self) -> usize {
-        f64::max(
-            (self.total_population as f64) / self.average_workplace_size,
-            1.,
-        ) as usize
-    }
-}
-
 #[wasm_bindgen]
 impl Config {
     pub fn default_config() -> JsValue {
-        JsValue::from_serde(&Config::default()).unwrap()
+        <JsValue as JsValueSerdeExt>::from_serde(&Config::default()).unwrap()
     }
 }
 
-macro_rules! log {
-    ($($t:tt)*) => {
-        web_sys::console::log_1(&format!($($t)*).into());
+impl Config {
+    fn rng(&self) -> Pcg64 {
+        match self.seed {
+            Some(seed) => Pcg64::seed_from_u64(seed),
+            None => Pcg64::from_entropy(),
+        }
     }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Serialize, Deserialize, Clone, Copy)]
 enum State {
     Susceptible,
+    Exposed(usize),
     Infected(usize),
+    Asymptomatic(usize),
     Detected(usize),
     Severe(usize),
     Unattended,
     Immune(usize),
     ImmuneDetected(usize),
     Dead,
+    // One dose of a two dose schedule.
+    PartiallyVaccinated(usize),
+    Vaccinated(usize),
 }
 
 impl State {
     fn index(&self) -> usize {
         match self {
             State::Susceptible => 0,
-            State::Infected(_) => 1,
-            State::Detected(_) => 2,
-            State::Severe(_) => 3,
-            State::Unattended => 4,
-            State::Immune(_) => 5,
-            State::ImmuneDetected(_) => 6,
-            State::Dead => 7,
+            State::Exposed(_) => 1,
+            State::Infected(_) => 2,
+            State::Asymptomatic(_) => 3,
+            State::Detected(_) => 4,
+            State::Severe(_) => 5,
+            State::Unattended => 6,
+            State::Immune(_) => 7,
+            State::ImmuneDetected(_) => 8,
+            State::Dead => 9,
+            State::PartiallyVaccinated(_) => 10,
+            State::Vaccinated(_) => 11,
         }
     }
 }
 
+// Immutable graph in compressed sparse row format: the neighbours of node `n`
+// are `neighbours[offsets[n]..offsets[n + 1]]`. Imported graphs can have a
+// weight per link, multiplying the probability of transmission through it,
+// in the same positions as `neighbours`. Otherwise `weights` is empty.
+#[derive(Serialize, Deserialize, Clone)]
 struct Graph {
-    left_nodes: Vec<FxHashSet<usize>>,
-    right_nodes: Vec<FxHashSet<usize>>,
+    offsets: Vec<usize>,
+    neighbours: Vec<u32>,
+    weights: Vec<f64>,
 }
 
 impl Graph {
-    fn new() -> Graph {
-        let left_nodes = Vec::new();
-        let right_nodes = Vec::new();
+    fn iternodes(&self, n: usize) -> impl Iterator<Item = usize> + '_ {
+        self.neighbours[self.offsets[n]..self.offsets[n + 1]]
+            .iter()
+            .map(|x| *x as usize)
+    }
+
+    // The neighbours of `n` with the weights of the links.
+    fn iterweighted(&self, n: usize) -> impl Iterator<Item = (usize, f64)> + '_ {
+        let range = self.offsets[n]..self.offsets[n + 1];
+        let weights = self.weights.get(range.clone());
+        self.neighbours[range]
+            .iter()
+            .enumerate()
+            .map(move |(k, x)| (*x as usize, weights.map_or(1., |w| w[k])))
+    }
+
+    // Build a graph of `n` nodes from a list of links, with optional
+    // weights.
+    fn from_edges(n: usize, edges: &[(u32, u32)], weights: Option<&[f64]>) -> Graph {
+        let mut offsets = vec![0; n + 1];
+        for (i, j) in edges.iter() {
+            offsets[*i as usize + 1] += 1;
+            offsets[*j as usize + 1] += 1;
+        }
+        for i in 0..n {
+            offsets[i + 1] += offsets[i];
+        }
+        let mut next = offsets[..n].to_vec();
+        let mut neighbours = vec![0; offsets[n]];
+        let mut res_weights = vec![0.; if weights.is_some() { offsets[n] } else { 0 }];
+        for (k, (i, j)) in edges.iter().enumerate() {
+            for (a, b) in [(*i, *j), (*j, *i)].iter() {
+                let pos = next[*a as usize];
+                neighbours[pos] = *b;
+                if let Some(w) = weights {
+                    res_weights[pos] = w[k];
+                }
+                next[*a as usize] += 1;
+            }
+        }
         Graph {
-            left_nodes,
-            right_nodes,
+            offsets,
+            neighbours,
+            weights: res_weights,
         }
     }
 
-    fn with_capacity(n: usize) -> Self{
-        let left_nodes = Vec::with_capacity(n);
-        let right_nodes = Vec::with_capacity(n);
-        Graph {
-            left_nodes,
-            right_nodes,
+    // The links as pairs with the lower node first, and their weights.
+    fn edges(&self) -> (Vec<(u32, u32)>, Option<Vec<f64>>) {
+        let n = self.offsets.len() - 1;
+        let mut edges = Vec::new();
+        let mut weights = Vec::new();
+        for i in 0..n {
+            for (j, w) in self.iterweighted(i) {
+                if i < j {
+                    edges.push((i as u32, j as u32));
+                    weights.push(w);
+                }
+            }
         }
+        let weights = if self.weights.is_empty() {
+            None
+        } else {
+            Some(weights)
+        };
+        (edges, weights)
     }
+}
 
+// Graph under construction. All the graphs are built adding nodes one at a
+// time and linking each new node to some of the previous ones, so only the
+// links to lower nodes are stored until the graph is finished.
+struct GraphBuilder {
+    offsets: Vec<usize>,
+    lower: Vec<u32>,
+}
 
-    fn register_node(&mut self) -> usize {
-        self.left_nodes.push(Default::default());
-        self.right_nodes.push(Default::default());
-        return self.left_nodes.len() - 1;
+impl GraphBuilder {
+    fn with_capacity(n: usize) -> Self {
+        let mut offsets = Vec::with_capacity(n + 1);
+        offsets.push(0);
+        GraphBuilder {
+            offsets,
+            lower: Vec::new(),
+        }
     }
 
-    fn add_link(&mut self, i: usize, j: usize) -> Option<()> {
-        if j < i {
-            self.left_nodes.get_mut(i)?.insert(j);
-            self.right_nodes.get_mut(j)?.insert(i);
-        } else {
-            self.left_nodes.get_mut(j)?.insert(i);
-            self.right_nodes.get_mut(i)?.insert(j);
+    fn register_node(&mut self) -> usize {
+        let n = self.offsets.len() - 1;
+        assert!(n < u32::MAX as usize, "Too many nodes");
+        self.offsets.push(self.lower.len());
+        n
+    }
+
+    // One of the nodes must be the last registered one.
+    fn add_link(&mut self, i: usize, j: usize) {
+        let last = self.offsets.len() - 2;
+        debug_assert!(usize::max(i, j) == last && i != j);
+        self.lower.push(usize::min(i, j) as u32);
+        *self.offsets.last_mut().unwrap() += 1;
+    }
+
+    fn build(self) -> Graph {
+        let n = self.offsets.len() - 1;
+        let mut degree = vec![0; n];
+        for i in 0..n {
+            let lower = &self.lower[self.offsets[i]..self.offsets[i + 1]];
+            degree[i] += lower.len();
+            for j in lower {
+                degree[*j as usize] += 1;
+            }
         }
-        Some(())
-    }
-    fn remove_link(&mut self, i: usize, j: usize) -> Option<()> {
-        if j < i {
-            self.left_nodes.get_mut(i)?.remove(&j);
-            self.right_nodes.get_mut(j)?.remove(&i);
-        } else {
-            self.left_nodes.get_mut(j)?.remove(&i);
-            self.right_nodes.get_mut(i)?.remove(&j);
+        let mut offsets = Vec::with_capacity(n + 1);
+        offsets.push(0);
+        for d in degree.iter() {
+            offsets.push(offsets.last().unwrap() + d);
+        }
+        // Next free position of each node.
+        let mut next = offsets[..n].to_vec();
+        let mut neighbours = vec![0; *offsets.last().unwrap()];
+        for i in 0..n {
+            for j in self.lower[self.offsets[i]..self.offsets[i + 1]].iter() {
+                neighbours[next[i]] = *j;
+                next[i] += 1;
+                neighbours[next[*j as usize]] = i as u32;
+                next[*j as usize] += 1;
+            }
+        }
+        Graph {
+            offsets,
+            neighbours,
+            weights: Vec::new(),
         }
-        Some(())
-    }
-
-    fn iternodes(&self, n: usize) -> iter::Chain<hash_set::Iter<usize>, hash_set::Iter<usize>> {
-        return self.left_nodes[n].iter().chain(self.right_nodes[n].iter());
     }
+}
 
-    fn len(&self) -> usize{
-        self.left_nodes.len()
-    }
+const NSTATES: usize = 12;
 
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Counter {
+    pub abs_counter: [i32; NSTATES],
+    pub day_counter: [i32; NSTATES],
+    pub by_network: NetworkCounter,
 }
 
+// New infections split by the contact layer they went through and by whether
+// the infector was detected, for the last day and since the start. The
+// infections through the layer `k` are at `2 * k` when the infector was
+// undetected and at `2 * k + 1` when it was detected.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct NetworkCounter {
+    pub layers: Vec<String>,
+    pub day_counter: Vec<i32>,
+    pub total_counter: Vec<i32>,
+}
 
-fn er_random_graph(n: usize, p: f64) -> Graph{
-    let mut rng = rand::thread_rng();
-    let mut g = Graph::with_capacity(n);
-    for i in 0..n{
-        g.register_node();
-        let nconnections = Binomial::new(i as u64, p)
-            .unwrap()
-            .sample(&mut rng) as usize;
-        let connections = rand::seq::index::sample(&mut rng, i, nconnections);
-        for c in connections.iter() {
-            g.add_link(c, i);
+impl NetworkCounter {
+    fn new(layers: &[String]) -> NetworkCounter {
+        NetworkCounter {
+            layers: layers.to_vec(),
+            day_counter: vec![0; 2 * layers.len()],
+            total_counter: vec![0; 2 * layers.len()],
         }
     }
-    g
-}
 
-#[derive(Serialize, Deserialize, Debug)]
-struct Counter {
-    abs_counter: [i32; 8],
-    day_counter: [i32; 8],
+    // `<layer>_undetected` and `<layer>_detected` for every layer, in the
+    // order of the counts.
+    pub fn names(&self) -> Vec<String> {
+        self.layers
+            .iter()
+            .flat_map(|l| vec![format!("{}_undetected", l), format!("{}_detected", l)])
+            .collect()
+    }
+
+    fn register(&mut self, layer: usize, detected: bool) {
+        let index = 2 * layer + detected as usize;
+        self.day_counter[index] += 1;
+        self.total_counter[index] += 1;
+    }
 }
 
 impl Counter {
-    fn new() -> Counter {
-        let abs_counter = [0, 0, 0, 0, 0, 0, 0, 0];
-        let day_counter = [0, 0, 0, 0, 0, 0, 0, 0];
+    pub const NAMES: [&'static str; NSTATES] = [
+        "susceptible",
+        "exposed",
+        "infected",
+        "asymptomatic",
+        "detected",
+        "severe",
+        "unattended",
+        "immune",
+        "immune_detected",
+        "dead",
+        "partially_vaccinated",
+        "vaccinated",
+    ];
+
+    fn new(layers: &[String]) -> Counter {
+        let abs_counter = [0; NSTATES];
+        let day_counter = [0; NSTATES];
         Counter {
             abs_counter,
             day_counter,
+            by_network: NetworkCounter::new(layers),
         }
     }
     fn register(&mut self, s: State) {
         self.abs_counter[s.index()] += 1;
     }
+    fn unregister(&mut self, s: State) {
+        self.abs_counter[s.index()] -= 1;
+    }
     fn state_count(&self, s: State) -> i32 {
         self.abs_counter[s.index()]
     }
@@ -285,13 +570,25 @@ impl Counter {
             self.day_counter[to.index()] += 1;
         }
     }
+    /// Number of agents that can pass on the disease now or in the future.
+    pub fn active_infections(&self) -> i32 {
+        self.state_count(State::Exposed(0))
+            + self.state_count(State::Infected(0))
+            + self.state_count(State::Asymptomatic(0))
+            + self.state_count(State::Detected(0))
+    }
+
     fn reset_day_counter(&mut self) {
         for i in self.day_counter.iter_mut() {
             *i = 0
         }
+        for i in self.by_network.day_counter.iter_mut() {
+            *i = 0
+        }
     }
 }
 
+#[derive(Serialize, Deserialize, Clone)]
 struct Averager {
     sum: i32,
     count: usize,
@@ -317,40 +614,20 @@ impl Averager {
     fn get(&self) -> f64 {
         self.sum as f64 / self.count as f64
     }
-}
 
-#[derive(Default)]
-struct ChainSet {
-    data: VecDeque<FxHashSet<usize>>,
+    fn merge(&mut self, other: &Averager) {
+        self.sum += other.sum;
+        self.count += other.count;
+    }
 }
 
-struct PartialDrainChainset<'a> {
-    chainset: &'a mut ChainSet,
-    drain: Option<Vec<usize>>,
-    n: usize,
-}
+// A set of agents that keeps the insertion order, so that the agents are
+// always taken in the same order, also after restoring a snapshot.
+type AgentSet = IndexSet<usize, BuildHasherDefault<FxHasher>>;
 
-impl Iterator for PartialDrainChainset<'_> {
-    type Item = usize;
-    fn next(&mut self) -> Option<usize> {
-        if let Some(drain) = self.drain.as_mut() {
-            if let Some(val) = drain.pop() {
-                self.n -= 1;
-                return Some(val);
-            }
-        }
-        while self.chainset.data.front()?.is_empty() {
-            self.chainset.pop_child();
-        }
-        let set = self.chainset.data.front_mut()?;
-        if self.n <= set.len() {
-            self.drain = Some(set.drain().collect());
-            self.chainset.pop_child();
-            return self.next();
-        } else {
-            return self.chainset.pop_clean();
-        }
-    }
+#[derive(Default, Serialize, Deserialize, Clone)]
+struct ChainSet {
+    data: VecDeque<AgentSet>,
 }
 
 impl ChainSet {
@@ -358,7 +635,7 @@ impl ChainSet {
         self.data.push_back(Default::default());
     }
 
-    fn push_child(&mut self, s: FxHashSet<usize>) {
+    fn push_child(&mut self, s: AgentSet) {
         self.data.push_back(s);
     }
 
@@ -371,7 +648,7 @@ impl ChainSet {
         if self.data.is_empty() {
             self.add_child();
         }
-        return self.data.back_mut().unwrap().insert(value);
+        self.data.back_mut().unwrap().insert(value)
     }
 
     fn contains(&self, value: usize) -> bool {
@@ -385,18 +662,16 @@ impl ChainSet {
 
     fn remove(&mut self, value: usize) -> bool {
         for set in self.data.iter_mut() {
-            if set.remove(&value) {
+            if set.swap_remove(&value) {
                 return true;
             }
         }
-        return false;
+        false
     }
 
     fn pop(&mut self) -> Option<usize> {
         for set in self.data.iter_mut() {
-            if !set.is_empty() {
-                let ele = set.iter().next().unwrap().clone();
-                set.remove(&ele);
+            if let Some(ele) = set.pop() {
                 return Some(ele);
             }
         }
@@ -429,106 +704,69 @@ impl ChainSet {
     }
 }
 
+// Contacts waiting to be tested, with one queue per tracing priority. The
+// contacts in the queues with a lower priority are tested first and take the
+// place of the others when the queues are full.
+#[derive(Serialize, Deserialize, Clone)]
 struct TestQueue {
     maxsize: usize,
-    family_queue: ChainSet,
-    workplace_queue: ChainSet,
-    world_queue: ChainSet,
+    queues: Vec<ChainSet>,
     recently_tested: ChainSet,
 }
 
 impl TestQueue {
-    fn new(maxsize: usize) -> Self {
-        let family_queue = Default::default();
-        let workplace_queue = Default::default();
-        let world_queue = Default::default();
+    fn new(maxsize: usize, npriorities: usize) -> Self {
+        let mut queues = Vec::with_capacity(npriorities);
+        queues.resize_with(npriorities, Default::default);
         let recently_tested = Default::default();
         Self {
             maxsize,
-            family_queue,
-            workplace_queue,
-            world_queue,
+            queues,
             recently_tested,
         }
     }
 
-    fn family_full(&self) -> bool {
-        self.family_queue.len() >= self.maxsize
-    }
-
-    fn workplace_full(&self) -> bool {
-        self.workplace_queue.len() >= self.maxsize - self.family_queue.len()
-    }
-
-    fn world_full(&self) -> bool {
-        self.world_queue.len()
-            >= self.maxsize - self.family_queue.len() - self.workplace_queue.len()
+    fn full(&self, priority: usize) -> bool {
+        let before: usize = self.queues[..priority].iter().map(|q| q.len()).sum();
+        self.queues[priority].len() >= self.maxsize.saturating_sub(before)
     }
 
     fn len(&self) -> usize {
-        self.family_queue.len() + self.workplace_queue.len() + self.world_queue.len()
-    }
-
-    fn insert_family(&mut self, value: usize) -> bool {
-        if self.family_full() {
-            return false;
-        }
-        if self.recently_tested.contains(value) {
-            return false;
-        }
-        let b = self.workplace_queue.remove(value) | self.world_queue.remove(value);
-        if !b && self.len() > self.maxsize - 1 {
-            if !self.world_queue.is_empty() {
-                self.world_queue.pop_clean();
-            } else {
-                self.workplace_queue.pop_clean().unwrap();
-            }
-        }
-        self.family_queue.insert(value)
+        self.queues.iter().map(|q| q.len()).sum()
     }
 
-    fn insert_workplace(&mut self, value: usize) -> bool {
-        if self.workplace_full() {
+    fn insert(&mut self, priority: usize, value: usize) -> bool {
+        if self.full(priority) {
             return false;
         }
-        if self.family_queue.contains(value) {
+        if self.queues[..priority].iter().any(|q| q.contains(value)) {
             return false;
         }
         if self.recently_tested.contains(value) {
             return false;
         }
-        let b = self.world_queue.remove(value);
-        if !b && self.len() > self.maxsize - 1 {
-            self.world_queue.pop_clean().unwrap();
-        }
-        self.workplace_queue.insert(value)
-    }
-
-    fn insert_world(&mut self, value: usize) -> bool {
-        if self.world_full() {
-            return false;
-        }
-        if self.family_queue.contains(value) || self.workplace_queue.contains(value) {
-            return false;
+        let mut b = false;
+        for q in self.queues[priority + 1..].iter_mut() {
+            b |= q.remove(value);
         }
-        if self.recently_tested.contains(value) {
-            return false;
+        if !b && self.len() > self.maxsize - 1 {
+            if let Some(q) = self.queues[priority + 1..]
+                .iter_mut()
+                .rev()
+                .find(|q| !q.is_empty())
+            {
+                q.pop_clean();
+            }
         }
-        self.world_queue.insert(value)
+        self.queues[priority].insert(value)
     }
 
     fn tick(&mut self, time: usize) {
         if time > 1 {
             self.recently_tested.pop_child();
         }
-        for g in [
-            &mut self.family_queue,
-            &mut self.workplace_queue,
-            &mut self.world_queue,
-        ]
-        .iter_mut()
-        {
-            while g.data.front().map_or(false, |v| v.is_empty()) {
+        for g in self.queues.iter_mut() {
+            while g.data.front().is_some_and(|v| v.is_empty()) {
                 g.pop_child();
             }
             g.add_child();
@@ -552,289 +790,328 @@ impl<T: PartialEq> RemoveItem<T> for SmallVec<[T;4]>
     }
 }
 
+// Everything is serialized, so that a snapshot continues exactly like the
+// original simulation.
 #[wasm_bindgen]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Simulation {
-    family_graph: Graph,
-    workplace_graph: Graph,
-    world_graph: Graph,
+    layers: Vec<Layer>,
     counter: Counter,
     states: Vec<State>,
-    worker_workplaces: Vec<usize>,
+    // Whether the current or last infection of each agent is asymptomatic.
+    // Needed because detected asymptomatic agents become Detected.
+    asymptomatic: Vec<bool>,
+    // Index of the age band of each agent in `config.age_bands`.
+    ages: Vec<usize>,
+    age_counters: Vec<Counter>,
+    // Reduction of the worsening probability of the current infection, given
+    // by the vaccine.
+    severe_protection: Vec<f64>,
+    vaccination: Vaccination,
+    // Index of the strain of the last infection of each agent, if any, with 0
+    // being the original strain.
+    strain: Vec<Option<usize>>,
+    // Agents whose strain changed today, with the previous one.
+    strain_changes: Vec<(usize, Option<usize>)>,
+    // Agents infected today as the seeds of new strains.
+    seeded_today: i32,
+    // The states that agents went through today before their last one, with
+    // their strain at the time.
+    passed_states: Vec<(usize, State, Option<usize>)>,
+    active: ActiveAgents,
+    strain_counters: Vec<Counter>,
+    // Every infection since `record_infections` was called.
+    infection_log: Option<Vec<InfectionEvent>>,
+    history: Vec<HistoryEntry>,
     infections_caused: Vec<usize>,
     r_average: Averager,
     serial_interval_average: Averager,
     config: Config,
     test_queue: TestQueue,
-    family_contact_undetected_coef_mod: SmallVec<[f64; 4]>,
-    family_contact_detected_coef_mod: SmallVec<[f64; 4]>,
-    workplace_contact_undetected_coef_mod: SmallVec<[f64; 4]>,
-    workplace_contact_detected_coef_mod: SmallVec<[f64; 4]>,
-    world_contact_undetected_coef_mod: SmallVec<[f64; 4]>,
-    world_contact_detected_coef_mod: SmallVec<[f64; 4]>,
-    world_connections_reduction_mod: SmallVec<[f64; 4]>,
-    workplace_connections_reduction_mod: SmallVec<[usize; 4]>,
     max_daily_tests_mod: SmallVec<[usize; 4]>,
+    policies: PolicyEngine,
+    rng: Pcg64,
     time: usize,
 }
 
 impl Simulation {
-    pub fn new(config: Config) -> Simulation {
+    // Start a simulation, or return the problems with the configuration.
+    pub fn try_new(config: Config) -> Result<Simulation, Vec<ConfigError>> {
+        let errors = config.validate();
+        if errors.is_empty() {
+            Ok(Simulation::new(config))
+        } else {
+            Err(errors)
+        }
+    }
+
+    // Start a simulation with a configuration that is known to be valid.
+    pub(crate) fn new(config: Config) -> Simulation {
         utils::set_panic_hook();
 
-        let mut rng = rand::thread_rng();
+        let mut rng = config.rng();
         let family_sampler = WeightedIndex::new(config.family_size_weights.clone()).unwrap();
+        let age_sampler = AgeSampler::new(
+            &config.age_bands,
+            &config.family_sizes,
+            &config.family_size_weights,
+        );
 
-        let mut counter = Counter::new();
-
-        let mut family_graph = Graph::new();
-        let mut workplace_graph = Graph::new();
-
-        let nworkplaces = config.nworkplaces();
-        let mut workplaces: Vec<Vec<usize>> = Vec::with_capacity(nworkplaces);
-        let mut worker_workplaces = Vec::with_capacity(config.total_population + 10);
-        workplaces.resize_with(nworkplaces, Default::default);
-
-        let mut states: Vec<State> = Vec::new();
-
-        let mut nnodes = 0;
-        while nnodes < config.total_population {
-            let fsize_index = family_sampler.sample(&mut rand::thread_rng());
+        let mut households = Vec::with_capacity(config.total_population + 10);
+        let mut ages = Vec::with_capacity(config.total_population + 10);
+        let mut nhouseholds = 0;
+        while households.len() < config.total_population {
+            let fsize_index = family_sampler.sample(&mut rng);
             let fsize = config.family_sizes[fsize_index];
             for id_f in 0..fsize {
-                let g_index = family_graph.register_node();
-
-                for prev_g_index in (g_index - id_f)..g_index {
-                    family_graph.add_link(prev_g_index, g_index);
-                }
-
-                let workplace: usize = rng.gen_range(0, nworkplaces);
-                worker_workplaces.push(workplace);
-                let _ = workplace_graph.register_node();
-                let workplace_nodes = &workplaces[workplace];
-                let nconnections =
-                    Binomial::new(workplace_nodes.len() as u64, config.workplace_connectivity)
-                        .unwrap()
-                        .sample(&mut rng) as usize;
-                let connections =
-                    rand::seq::index::sample(&mut rng, workplace_nodes.len(), nconnections);
-                for c in connections.iter() {
-                    workplace_graph.add_link(g_index, workplace_nodes[c]);
-                }
-
-                workplaces[workplace].push(g_index);
-
-                let s = State::Susceptible;
-                counter.register(s);
-                states.push(s);
-                nnodes += 1;
+                households.push(nhouseholds);
+                ages.push(age_sampler.sample(&mut rng, id_f));
             }
+            nhouseholds += 1;
         }
 
-        let world_p = f64::min(
-            (config.average_world_connections) / (nnodes as f64),
-            1.,
-        );
-        let world_graph = er_random_graph(nnodes, world_p);
+        let layers: Vec<Layer> = config
+            .layers()
+            .into_iter()
+            .map(|spec| Layer::new(spec, &config, &households, &ages, None, &mut rng))
+            .collect();
+        Simulation::with_layers(config, rng, ages, layers)
+    }
+
+    // Start a simulation with the given agents and contact layers.
+    pub(crate) fn with_layers(
+        config: Config,
+        mut rng: Pcg64,
+        ages: Vec<usize>,
+        layers: Vec<Layer>,
+    ) -> Simulation {
+        let nnodes = ages.len();
+        let layer_names: Vec<String> = layers.iter().map(|l| l.spec.name.clone()).collect();
+        let mut counter = Counter::new(&layer_names);
+        let mut age_counters = vec![Counter::new(&layer_names); config.age_bands.len()];
+        let mut states = vec![State::Susceptible; nnodes];
+        for age in ages.iter() {
+            counter.register(State::Susceptible);
+            age_counters[*age].register(State::Susceptible);
+        }
+
+        // A queue for each distinct tracing priority, whatever its value.
+        let mut priorities: Vec<usize> = layers.iter().map(|l| l.spec.tracing_priority).collect();
+        priorities.sort_unstable();
+        priorities.dedup();
+        let npriorities = priorities.len();
 
         let initial_outbreak_size = usize::min(nnodes, config.initial_outbreak_size);
+        let mut strain = vec![None; nnodes];
+        let mut strain_counters = vec![Counter::new(&layer_names); config.nstrains()];
         let infected = rand::seq::index::sample(&mut rng, states.len(), initial_outbreak_size);
         for j in infected.iter() {
+            strain[j] = Some(0);
+            strain_counters[0].register(State::Infected(0));
+            strain_counters[0].day_counter[State::Infected(0).index()] += 1;
             states[j] = State::Infected(0);
             counter.transit(State::Susceptible, State::Infected(0));
+            age_counters[ages[j]].transit(State::Susceptible, State::Infected(0));
         }
         let mut infections_caused = Vec::with_capacity(nnodes);
         infections_caused.resize_with(nnodes, Default::default);
+        let asymptomatic = vec![false; nnodes];
+        let severe_protection = vec![0.; nnodes];
         let r_average = Averager::new();
         let serial_interval_average = Averager::new();
         let time = 0;
-        let test_queue = TestQueue::new(0);
+        let test_queue = TestQueue::new(0, npriorities);
         let max_daily_tests_mod = Default::default();
-        let family_contact_undetected_coef_mod = Default::default();
-        let family_contact_detected_coef_mod = Default::default();
-        let workplace_contact_undetected_coef_mod = Default::default();
-        let workplace_contact_detected_coef_mod = Default::default();
-        let world_contact_undetected_coef_mod = Default::default();
-        let world_contact_detected_coef_mod = Default::default();
-        let world_connections_reduction_mod = Default::default();
-        let workplace_connections_reduction_mod = Default::default();
-        Simulation {
+        let mut simulation = Simulation {
             time,
-            family_graph,
-            workplace_graph,
-            world_graph,
+            layers,
             counter,
-            worker_workplaces,
             infections_caused,
             r_average,
             serial_interval_average,
             states,
+            asymptomatic,
+            ages,
+            age_counters,
+            severe_protection,
+            vaccination: Default::default(),
+            strain,
+            strain_changes: Vec::new(),
+            seeded_today: 0,
+            passed_states: Vec::new(),
+            active: Default::default(),
+            strain_counters,
+            infection_log: None,
+            history: Vec::new(),
             test_queue,
             max_daily_tests_mod,
-            family_contact_undetected_coef_mod,
-            family_contact_detected_coef_mod,
-            workplace_contact_undetected_coef_mod,
-            workplace_contact_detected_coef_mod,
-            world_contact_undetected_coef_mod,
-            world_contact_detected_coef_mod,
-            workplace_connections_reduction_mod,
-            world_connections_reduction_mod,
+            policies: Default::default(),
+            rng,
             config,
+        };
+        for i in infected.iter() {
+            simulation.track(i, State::Susceptible, State::Infected(0));
         }
-    }
-}
-
-#[wasm_bindgen]
-impl Simulation {
-    pub fn from_js(config: JsValue) -> Option<Simulation> {
-        match config.into_serde() {
-            Ok(c) => Some(Simulation::new(c)),
-            Err(_) => None,
-        }
+        simulation.record_history();
+        simulation
     }
 
-    pub fn tick(&mut self) {
-        self.counter.reset_day_counter();
-        self.r_average.reset();
-        self.serial_interval_average.reset();
-        self.test_queue.tick(self.time);
-        let mut newstates: Vec<State> = Vec::with_capacity(self.states.len());
-
-        //Don't iterate over state here so we can mutably borrow `self` later
-        for i in 0..self.states.len() {
-            let s = self.states[i];
-            let newstate = match s {
-                State::Susceptible => self.get_infected(i),
-                State::Infected(t) => self.transit_infected(t, i),
-                State::Detected(t) => self.transit_detected(t, i),
-                State::Unattended => self.transit_unattended(),
-                State::Severe(t) => self.transit_severe(t),
-                State::Immune(t) => self.transit_immune(t),
-                State::ImmuneDetected(t) => self.transit_immune_detected(t),
-                State::Dead => State::Dead,
-            };
-            newstates.push(newstate);
-        }
-        self.trace_contacts(&mut newstates);
-        self.states = newstates;
-        self.time += 1;
+    pub fn counter(&self) -> &Counter {
+        &self.counter
     }
 
-    pub fn get_counter(&self) -> JsValue {
-        JsValue::from_serde(&self.counter).unwrap()
+    // The counter restricted to each age band, in the order of
+    // `config.age_bands`.
+    pub fn counter_by_age(&self) -> Vec<AgeCounter> {
+        self.config
+            .age_bands
+            .iter()
+            .zip(self.age_counters.iter())
+            .map(|(band, counter)| AgeCounter {
+                age_band: band.name.clone(),
+                counter: counter.clone(),
+            })
+            .collect()
     }
 
-    pub fn get_daily_r(&self) -> f64 {
-        self.r_average.get()
-    }
-
-    pub fn get_daily_serial_interval(&self) -> f64 {
-        self.serial_interval_average.get()
+    // Fails if the policy names a layer that the simulation doesn't have.
+    pub fn add_policy(&mut self, policy: Policy) -> Result<(), Vec<ConfigError>> {
+        let errors = self.config.validate_policies(std::slice::from_ref(&policy));
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        self.policies.add(policy);
+        Ok(())
     }
 
-    pub fn get_hospital_capacity(&self) -> usize {
-        self.config.hospital_capacity
+    // The policies applied or reversed in the last tick.
+    pub fn policy_events(&self) -> &[PolicyEvent] {
+        self.policies.events()
     }
 
-    pub fn get_time(&self) -> usize {
-        self.time
+    // Number of agents infected in the last day, including the seeds of new
+    // strains.
+    pub(crate) fn new_infections(&self) -> i32 {
+        let day = |state: State| self.counter.day_counter[state.index()];
+        // New infections go to Exposed when there is a latent period, but
+        // the seeds go straight to Infected.
+        if self.config.exposed_infected_profile.is_empty() {
+            day(State::Infected(0)) + day(State::Asymptomatic(0))
+        } else {
+            day(State::Exposed(0)) + self.seeded_today
+        }
     }
 
-    pub fn disable_fraction_of_workplaces(&mut self, fraction: f64) {
-        self.workplace_connections_reduction_mod.push((fraction * self.config.nworkplaces() as f64) as usize);
+    // The names of the policies in effect, repeated if a policy of the same
+    // kind has been applied more than once.
+    pub fn active_policies(&self) -> Vec<&'static str> {
+        self.policies.in_effect().collect()
     }
+}
 
-    pub fn undo_disable_fraction_of_workplaces(&mut self, fraction: f64) -> bool {
-        let index = (fraction * self.config.nworkplaces() as f64) as usize;
-        self.workplace_connections_reduction_mod.remove_item(&index)
+#[wasm_bindgen]
+impl Simulation {
+    // On failure, return a list of `{field, message}` objects describing what
+    // is wrong with the configuration.
+    pub fn from_js(config: JsValue) -> Result<Simulation, JsValue> {
+        let errors: Vec<ErrorMessage> = match JsValueSerdeExt::into_serde(&config) {
+            Ok(c) => match Simulation::try_new(c) {
+                Ok(s) => return Ok(s),
+                Err(errors) => errors.iter().map(ErrorMessage::from).collect(),
+            },
+            Err(e) => vec![ErrorMessage {
+                field: None,
+                message: e.to_string(),
+            }],
+        };
+        Err(<JsValue as JsValueSerdeExt>::from_serde(&errors).unwrap())
     }
 
-    pub fn multiply_undetected_household_infectability(&mut self, coef: f64) {
-        self.family_contact_undetected_coef_mod.push(coef);
+    pub fn tick(&mut self) {
+        self.counter.reset_day_counter();
+        for c in self.age_counters.iter_mut().chain(self.strain_counters.iter_mut()) {
+            c.reset_day_counter();
+        }
+        self.seeded_today = 0;
+        self.r_average.reset();
+        self.serial_interval_average.reset();
+        self.test_queue.tick(self.time);
+        let (mut newstates, days) = if self.config.event_driven {
+            self.update_active_agents()
+        } else {
+            self.update_agents()
+        };
+        self.merge_days(days);
+        self.trace_contacts(&mut newstates);
+        self.vaccinate(&mut newstates);
+        self.seed_strains(&mut newstates);
+        self.apply_states(newstates);
+        self.time += 1;
+        PolicyEngine::run(self);
+        self.record_history();
     }
 
-    pub fn multiply_detected_household_infectability(&mut self, coef: f64) {
-        self.family_contact_detected_coef_mod.push(coef);
+    pub fn add_policies(&mut self, policies: JsValue) -> bool {
+        match JsValueSerdeExt::into_serde::<Vec<Policy>>(&policies) {
+            Ok(policies) if self.config.validate_policies(&policies).is_empty() => {
+                for p in policies {
+                    self.policies.add(p);
+                }
+                true
+            }
+            Ok(_) => false,
+            Err(_) => false,
+        }
     }
 
-    pub fn multiply_undetected_workplace_infectability(&mut self, coef: f64) {
-        self.workplace_contact_undetected_coef_mod.push(coef);
+    pub fn get_policy_events(&self) -> JsValue {
+        <JsValue as JsValueSerdeExt>::from_serde(self.policy_events()).unwrap()
     }
 
-    pub fn multiply_detected_workplace_infectability(&mut self, coef: f64) {
-        self.workplace_contact_detected_coef_mod.push(coef);
+    pub fn get_counter(&self) -> JsValue {
+        <JsValue as JsValueSerdeExt>::from_serde(&self.counter).unwrap()
     }
 
-    pub fn multiply_undetected_world_infectability(&mut self, coef: f64) {
-        self.world_contact_undetected_coef_mod.push(coef);
+    pub fn get_counter_by_age(&self) -> JsValue {
+        <JsValue as JsValueSerdeExt>::from_serde(&self.counter_by_age()).unwrap()
     }
 
-    pub fn multiply_detected_world_infectability(&mut self, coef: f64) {
-        self.world_contact_detected_coef_mod.push(coef);
+    pub fn get_counter_by_strain(&self) -> JsValue {
+        <JsValue as JsValueSerdeExt>::from_serde(&self.counter_by_strain()).unwrap()
     }
 
-    pub fn undo_multiply_undetected_household_infectability(&mut self, coef: f64) -> bool {
-        self.family_contact_undetected_coef_mod.remove_item(&coef)
+    pub fn start_infection_log(&mut self) {
+        self.record_infections();
     }
 
-    pub fn undo_multiply_detected_household_infectability(&mut self, coef: f64) -> bool {
-        self.family_contact_detected_coef_mod.remove_item(&coef)
+    pub fn get_infection_log(&self) -> JsValue {
+        <JsValue as JsValueSerdeExt>::from_serde(self.infection_log()).unwrap()
     }
 
-    pub fn undo_multiply_undetected_workplace_infectability(&mut self, coef: f64) -> bool {
-        self.workplace_contact_undetected_coef_mod.remove_item(&coef)
+    pub fn get_transmission_tree(&self) -> JsValue {
+        <JsValue as JsValueSerdeExt>::from_serde(&self.transmission_tree().to_json()).unwrap()
     }
 
-    pub fn undo_multiply_detected_workplace_infectability(&mut self, coef: f64) -> bool {
-        self.workplace_contact_detected_coef_mod.remove_item(&coef)
+    // A list with the entries of the history from `from_day` onwards.
+    pub fn get_history(&self, from_day: usize) -> JsValue {
+        let entries: Vec<&HistoryEntry> = self.history_from(from_day).collect();
+        <JsValue as JsValueSerdeExt>::from_serde(&entries).unwrap()
     }
 
-    pub fn undo_multiply_undetected_world_infectability(&mut self, coef: f64) -> bool {
-        self.world_contact_undetected_coef_mod.remove_item(&coef)
+    pub fn get_daily_r(&self) -> f64 {
+        self.r_average.get()
     }
 
-    pub fn undo_multiply_detected_world_infectability(&mut self, coef: f64) -> bool {
-        self.world_contact_detected_coef_mod.remove_item(&coef)
+    pub fn get_daily_serial_interval(&self) -> f64 {
+        self.serial_interval_average.get()
     }
 
-    pub fn disable_fraction_of_world_connections(&mut self, frac: f64) {
-        log!("Enter apply function: frac, {}", frac);
-        let currmax = if self.world_connections_reduction_mod.is_empty(){
-            0.
-        }else{
-            self.world_connections_reduction_mod.iter().fold(-f64::INFINITY, |a, &b| f64::max(a, b))
-        };
-        if frac > currmax{
-            let n = self.world_graph.len();
-            let p = f64::min(
-                (1.-frac)*(self.config.average_world_connections) / (n as f64),
-                1.);
-            log!("Rebuilding graph n {} p {}", n, p);
-            self.world_graph = er_random_graph(n, p);
-        }
-        self.world_connections_reduction_mod.push(frac);
+    pub fn get_hospital_capacity(&self) -> usize {
+        self.config.hospital_capacity
     }
 
-    pub fn undo_disable_fraction_of_world_connections(&mut self, frac: f64) -> bool {
-        log!("Enter undo function: frac, {}", frac);
-        if !self.world_connections_reduction_mod.remove_item(&frac){
-            return false
-        }
-        let currmax = if self.world_connections_reduction_mod.is_empty(){
-            0.
-        }else{
-            self.world_connections_reduction_mod.iter().fold(-f64::INFINITY, |a, &b| f64::max(a, b))
-        };
-        log!("currmax, {}", currmax);
-        if currmax < frac{
-            let n = self.world_graph.len();
-            let p = f64::min(
-                (1.-currmax)*(self.config.average_world_connections) / (n as f64),
-                1.);
-
-            log!("Rebuilding graph n {} p {}", n, p);
-            self.world_graph = er_random_graph(n, p);
-        }
-        true
+    pub fn get_time(&self) -> usize {
+        self.time
     }
 
     pub fn set_max_contact_tracing(&mut self, max: usize) {
@@ -849,76 +1126,148 @@ impl Simulation {
         let ret = self.max_daily_tests_mod.remove_item(&max);
         let newmax = self.get_max_daily_tests();
         self.test_queue.maxsize = newmax*3;
-        return ret;
+        ret
     }
 }
 
 impl Simulation {
-    fn get_infected(&mut self, i: usize) -> State {
-        let iterdata: [Option<(&Graph, f64, f64)>; 3] = [
-            Some((
-                &self.family_graph,
-                self.get_family_contact_undetected_coef(),
-                self.get_family_contact_detected_coef(),
-            )),
-            if self.workplace_enabled(i) {
-                None
-            } else {
-                Some((
-                    &self.workplace_graph,
-                    self.get_workplace_contact_undetected_coef(),
-                    self.get_workplace_contact_detected_coef(),
-                ))
-            },
-            Some((
-                &self.world_graph,
-                self.get_world_contact_undetected_coef(),
-                self.get_world_contact_detected_coef(),
-            )),
-        ];
-        for opt in iterdata.iter() {
-            if let Some((g, infected_coef, detected_coef)) = opt {
-                for n in g.iternodes(i) {
-                    let connected_state = self.states[*n];
-                    if let State::Infected(t) | State::Detected(t) = connected_state {
-                        let coef = if let State::Infected(_) = connected_state {
-                            infected_coef
-                        } else {
-                            detected_coef
-                        };
-                        if coef * sat_index(&self.config.susceptible_infected_profile, t)
-                            > rand::random()
-                        {
-                            let ns = State::Infected(0);
-                            self.infections_caused[*n] += 1;
-                            self.serial_interval_average.push(t as i32);
-                            self.counter.transit(State::Susceptible, ns);
-                            return ns;
-                        }
-                    }
-                }
+    fn update_agent(&self, day: &mut Day, i: usize) -> State {
+        let s = self.states[i];
+        if self.can_be_infected(s) {
+            if let Some(ns) = self.get_infected(day, i, s) {
+                return ns;
             }
         }
-        State::Susceptible
+        self.transit_agent(day, i)
     }
 
-    fn workplace_enabled(&self, i: usize) -> bool {
-        if let Some(val) = self.workplace_connections_reduction_mod.iter().max(){
-            self.worker_workplaces[i] < *val
-        }else{
-            true
+    fn can_be_infected(&self, s: State) -> bool {
+        match s {
+            State::Susceptible | State::PartiallyVaccinated(_) | State::Vaccinated(_) => true,
+            State::Immune(_) | State::ImmuneDetected(_) => self.config.reinfections(),
+            _ => false,
         }
     }
 
-    fn hospitals_full(&self) -> bool {
-        self.counter.state_count(State::Severe(0)) >= self.config.hospital_capacity as i32
+    // The transition of an agent that has not been infected today.
+    fn transit_agent(&self, day: &mut Day, i: usize) -> State {
+        let s = self.states[i];
+        match s {
+            State::Susceptible => State::Susceptible,
+            State::Exposed(t) => self.transit_exposed(day, t, i),
+            State::Infected(t) => self.transit_infected(day, t, i),
+            State::Asymptomatic(t) => self.transit_asymptomatic(day, t, i),
+            State::Detected(t) => self.transit_detected(day, t, i),
+            State::Unattended => self.transit_unattended(day),
+            State::Severe(t) => self.transit_severe(day, t, i),
+            State::Immune(t) => self.transit_immune(day, t),
+            State::ImmuneDetected(t) => self.transit_immune_detected(day, t),
+            State::Dead => State::Dead,
+            State::PartiallyVaccinated(t) => State::PartiallyVaccinated(t + 1),
+            State::Vaccinated(t) => self.transit_vaccinated(day, t),
+        }
+    }
+
+    fn susceptibility(&self, i: usize, s: State) -> f64 {
+        self.config.susceptibility_for(self.ages[i])
+            * (1. - self.vaccine_protection(s) * self.config.vaccine_efficacy_infection)
+    }
+
+    // Probability that agent `i`, in state `s`, is infected today by its
+    // contact `n`, or `None` if `n` is not infectious.
+    fn infection_probability(
+        &self,
+        i: usize,
+        s: State,
+        susceptibility: f64,
+        n: usize,
+        infected_coef: f64,
+        detected_coef: f64,
+    ) -> Option<f64> {
+        let connected_state = self.states[n];
+        let (coef, t) = match connected_state {
+            State::Infected(t) => (infected_coef, t),
+            State::Asymptomatic(t) => (infected_coef * self.config.asymptomatic_infectiousness, t),
+            State::Detected(t) => (detected_coef, t),
+            _ => return None,
+        };
+        let strain = self.strain_of(n);
+        let immunity = match s {
+            State::Immune(_) | State::ImmuneDetected(_) => {
+                self.config.cross_immunity(self.strain_of(i), strain)
+            }
+            _ => 0.,
+        };
+        let profile = self.config.susceptible_infected_profile_for(strain);
+        Some(coef * susceptibility * (1. - immunity) * sat_index(profile, t))
+    }
+
+    // Agent `i`, in state `s`, is infected by `n` through the layer `layer`.
+    fn infect(&self, day: &mut Day, i: usize, s: State, n: usize, layer: usize) -> State {
+        let t = match self.states[n] {
+            State::Infected(t) | State::Asymptomatic(t) | State::Detected(t) => t,
+            _ => unreachable!(),
+        };
+        // Only draw when needed so that runs without asymptomatic cases keep
+        // their trajectories.
+        let asymptomatic = self.config.asymptomatic_fraction > 0.
+            && self.config.asymptomatic_fraction > day.rng.gen();
+        let ns = if !self.config.exposed_infected_profile.is_empty() {
+            State::Exposed(0)
+        } else if asymptomatic {
+            State::Asymptomatic(0)
+        } else {
+            State::Infected(0)
+        };
+        day.infections.push(Infection {
+            agent: i,
+            infector: n,
+            layer,
+            infector_state: self.states[n],
+            strain: self.strain_of(n),
+            asymptomatic,
+            severe_protection: self.vaccine_protection(s) * self.config.vaccine_efficacy_severe,
+        });
+        day.serial_interval_average.push(t as i32);
+        day.counter.transit(s, ns);
+        ns
+    }
+
+    fn get_infected(&self, day: &mut Day, i: usize, s: State) -> Option<State> {
+        let susceptibility = self.susceptibility(i, s);
+        for (k, layer) in self.layers.iter().enumerate() {
+            if !self.uses_layer(k, i) {
+                continue;
+            }
+            let infected_coef = layer.undetected_coef();
+            let detected_coef = layer.detected_coef();
+            for (n, w) in layer.graph.iterweighted(i) {
+                if let Some(p) = self.infection_probability(
+                    i,
+                    s,
+                    susceptibility,
+                    n,
+                    w * infected_coef,
+                    w * detected_coef,
+                ) {
+                    if p > day.rng.gen() {
+                        return Some(self.infect(day, i, s, n, k));
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    fn hospitals_full(&self, counter: &Counter) -> bool {
+        counter.state_count(State::Severe(0)) >= self.config.hospital_capacity as i32
     }
 
     fn get_max_daily_tests(&self) -> usize{
         *self.max_daily_tests_mod.iter().max().unwrap_or(&0)
     }
 
-    fn sample_state(states: &[State], weights: &[f64]) -> State {
+    fn sample_state<R: Rng>(rng: &mut R, states: &[State], weights: &[f64]) -> State {
         let mut weights = weights.to_vec();
         // Compute probability of no transition, in a numerically stable way.
         // Product (1-p_i) = Exp(Sum(Log(1-p_i)))
@@ -934,56 +1283,62 @@ impl Simulation {
         };
 
         weights.push(rwpnotrans);
-        let mut rng = rand::thread_rng();
-        let index = WeightedIndex::new(weights).unwrap().sample(&mut rng);
+        let index = WeightedIndex::new(weights).unwrap().sample(rng);
         states[index]
     }
 
-    fn handle_r0(&mut self, i: usize, s: State) {
+    // Record the agents that stop being infectious, whose infections are
+    // added to the R average at the end of the day.
+    fn handle_r0(day: &mut Day, i: usize, s: State) {
         //Exhaustive match here is on purpose.
         match s {
             State::Unattended
             | State::Susceptible
+            | State::Exposed(_)
             | State::Severe(_)
             | State::Immune(_)
             | State::ImmuneDetected(_)
-            | State::Dead => {
-                let ninfected = self.infections_caused[i];
-                self.r_average.push(ninfected as i32);
-                self.infections_caused[i] = 0;
-            }
-            State::Infected(_) | State::Detected(_) => {}
+            | State::Dead
+            | State::PartiallyVaccinated(_)
+            | State::Vaccinated(_) => day.recovered.push(i),
+            State::Infected(_) | State::Asymptomatic(_) | State::Detected(_) => {}
         }
     }
 
-    fn trace_contacts_impl(
+    fn trace_contacts_impl(&mut self, newstates: &mut NewStates) -> (AgentSet, AgentSet) {
+        // The queues are taken out while the agents in them change state.
+        let mut queues = std::mem::take(&mut self.test_queue.queues);
+        let res = self.test_queued(&mut queues, newstates);
+        self.test_queue.queues = queues;
+        res
+    }
+
+    fn test_queued(
         &mut self,
-        newstates: &mut [State],
-    ) -> (FxHashSet<usize>, FxHashSet<usize>) {
-        let mut res: FxHashSet<usize> = Default::default();
-        let mut recently_tested: FxHashSet<usize> = Default::default();
+        queues: &mut [ChainSet],
+        newstates: &mut NewStates,
+    ) -> (AgentSet, AgentSet) {
+        let mut res: AgentSet = Default::default();
+        let mut recently_tested: AgentSet = Default::default();
         let mut n = self.get_max_daily_tests();
-        if n <= 0 {
+        if n == 0 {
             return (res, recently_tested);
         }
-        let mut queues = [
-            &mut self.test_queue.family_queue,
-            &mut self.test_queue.workplace_queue,
-            &mut self.test_queue.world_queue,
-        ];
-
         for q in queues.iter_mut() {
             for set in q.data.iter_mut() {
                 if set.len() >= n {
-                    for node in set.drain() {
-                        let s = newstates.get_mut(node).unwrap();
-                        if let State::Infected(t) = s {
-                            let news = State::Detected(*t);
-                            self.counter.transit(*s, news);
-                            *s = news;
+                    for node in set.drain(..) {
+                        let s = newstates.get(&self.states, node);
+                        if let State::Infected(t) | State::Asymptomatic(t) = s {
+                            self.set_state(newstates, node, State::Detected(t));
                             n -= 1;
                             res.insert(node);
-                        } else if let State::Susceptible | State::Immune(_) = s {
+                        } else if let State::Susceptible
+                        | State::Exposed(_)
+                        | State::Immune(_)
+                        | State::PartiallyVaccinated(_)
+                        | State::Vaccinated(_) = s
+                        {
                             recently_tested.insert(node);
                             n -= 1;
                         }
@@ -992,22 +1347,21 @@ impl Simulation {
                         }
                     }
                 } else {
-                    while let Some(node) = set.iter().next() {
-                        let node = *node;
-                        set.remove(&node);
-                        let s = newstates.get_mut(node).unwrap();
-                        if let State::Infected(t) = s {
-                            let news = State::Detected(*t);
-                            self.counter.transit(*s, news);
-                            *s = news;
+                    while let Some(node) = set.pop() {
+                        let s = newstates.get(&self.states, node);
+                        if let State::Infected(t) | State::Asymptomatic(t) = s {
+                            self.set_state(newstates, node, State::Detected(t));
                             n -= 1;
                             res.insert(node);
                         } else if let State::Immune(t) = s {
-                            let news = State::ImmuneDetected(*t);
-                            self.counter.transit(*s, news);
-                            *s = news;
+                            self.set_state(newstates, node, State::ImmuneDetected(t));
                             n -= 1;
-                        } else if let State::Susceptible | State::Immune(_) = s {
+                        } else if let State::Susceptible
+                        | State::Exposed(_)
+                        | State::Immune(_)
+                        | State::PartiallyVaccinated(_)
+                        | State::Vaccinated(_) = s
+                        {
                             recently_tested.insert(node);
                             n -= 1;
                         }
@@ -1018,10 +1372,10 @@ impl Simulation {
                 }
             }
         }
-        return (res, recently_tested);
+        (res, recently_tested)
     }
 
-    fn trace_contacts(&mut self, newstates: &mut [State]) {
+    fn trace_contacts(&mut self, newstates: &mut NewStates) {
         let (res, recently_tested) = self.trace_contacts_impl(newstates);
         self.test_queue.recently_tested.push_child(recently_tested);
         for i in res.iter() {
@@ -1031,43 +1385,64 @@ impl Simulation {
 
     fn queue_contact_tracing(&mut self, i: usize) {
         let do_queue = |s: State| match s {
-            State::Susceptible | State::Infected(_) | State::Immune(_) => true,
+            State::Susceptible
+            | State::Exposed(_)
+            | State::Infected(_)
+            | State::Asymptomatic(_)
+            | State::Immune(_)
+            | State::PartiallyVaccinated(_)
+            | State::Vaccinated(_) => true,
             State::Severe(_)
             | State::Detected(_)
             | State::Unattended
             | State::Dead
             | State::ImmuneDetected(_) => false,
         };
-        for n in self.family_graph.iternodes(i) {
-            if self.test_queue.family_full() {
-                return;
+        // When the queue of a layer is full, the ones of the layers with a
+        // higher value of the priority are too. The queues are numbered by
+        // the rank of the priority among the distinct ones.
+        let mut order: Vec<usize> = (0..self.layers.len()).collect();
+        order.sort_by_key(|k| self.layers[*k].spec.tracing_priority);
+        let mut priority = 0;
+        let mut previous = None;
+        for k in order {
+            let value = self.layers[k].spec.tracing_priority;
+            if previous.is_some_and(|p| p != value) {
+                priority += 1;
             }
-            if do_queue(self.states[*n]) {
-                self.test_queue.insert_family(*n);
+            previous = Some(value);
+            if !self.uses_layer(k, i) {
+                continue;
             }
-        }
-        if self.workplace_enabled(i) {
-            for n in self.workplace_graph.iternodes(i) {
-                if self.test_queue.workplace_full() {
+            for n in self.layers[k].graph.iternodes(i) {
+                if self.test_queue.full(priority) {
                     return;
                 }
-                if do_queue(self.states[*n]) {
-                    self.test_queue.insert_workplace(*n);
+                if do_queue(self.states[n]) {
+                    self.test_queue.insert(priority, n);
                 }
             }
         }
-        for n in self.world_graph.iternodes(i) {
-            if self.test_queue.world_full() {
-                return;
-            }
-            if do_queue(self.states[*n]) {
-                self.test_queue.insert_world(*n);
-            }
+    }
+
+    fn infectious_state(&self, i: usize) -> State {
+        if self.asymptomatic[i] {
+            State::Asymptomatic(0)
+        } else {
+            State::Infected(0)
         }
     }
 
-    fn transit_infected(&mut self, t: usize, i: usize) -> State {
-        let severe_state = if self.hospitals_full() {
+    fn transit_exposed(&self, day: &mut Day, t: usize, i: usize) -> State {
+        let opts = [self.infectious_state(i), State::Exposed(t + 1)];
+        let w = [sat_index(&self.config.exposed_infected_profile, t)];
+        let s = Simulation::sample_state(&mut day.rng, &opts, &w);
+        day.counter.transit(State::Exposed(0), s);
+        s
+    }
+
+    fn transit_infected(&self, day: &mut Day, t: usize, i: usize) -> State {
+        let severe_state = if self.hospitals_full(&day.counter) {
             State::Unattended
         } else {
             State::Severe(0)
@@ -1080,20 +1455,39 @@ impl Simulation {
         ];
         let w = [
             sat_index(&self.config.infected_immune_profile, t),
-            sat_index(&self.config.infected_detected_profile, t),
-            sat_index(&self.config.infected_severe_profile, t),
+            sat_index(self.config.infected_detected_profile_for(self.strain_of(i)), t),
+            sat_index(self.infected_severe_profile(i), t) * (1. - self.severe_protection[i]),
+        ];
+        let s = Simulation::sample_state(&mut day.rng, &opts, &w);
+        Simulation::handle_r0(day, i, s);
+        day.counter.transit(State::Infected(0), s);
+        if let State::Detected(_) = s {
+            day.detected.push(i);
+        }
+        s
+    }
+
+    fn transit_asymptomatic(&self, day: &mut Day, t: usize, i: usize) -> State {
+        let opts = [
+            State::Immune(0),
+            State::Detected(t + 1),
+            State::Asymptomatic(t + 1),
+        ];
+        let w = [
+            sat_index(&self.config.infected_immune_profile, t),
+            sat_index(&self.config.asymptomatic_detected_profile, t),
         ];
-        let s = Simulation::sample_state(&opts, &w);
-        self.handle_r0(i, s);
-        self.counter.transit(State::Infected(0), s);
+        let s = Simulation::sample_state(&mut day.rng, &opts, &w);
+        Simulation::handle_r0(day, i, s);
+        day.counter.transit(State::Asymptomatic(0), s);
         if let State::Detected(_) = s {
-            self.queue_contact_tracing(i)
+            day.detected.push(i);
         }
         s
     }
 
-    fn transit_detected(&mut self, t: usize, i: usize) -> State {
-        let severe_state = if self.hospitals_full() {
+    fn transit_detected(&self, day: &mut Day, t: usize, i: usize) -> State {
+        let severe_state = if self.hospitals_full(&day.counter) {
             State::Unattended
         } else {
             State::Severe(0)
@@ -1103,96 +1497,65 @@ impl Simulation {
             severe_state,
             State::Detected(t + 1),
         ];
-        let w = [
-            sat_index(&self.config.infected_immune_profile, t),
-            sat_index(&self.config.infected_severe_profile, t),
-        ];
-        let s = Simulation::sample_state(&opts, &w);
-        self.handle_r0(i, s);
-        self.counter.transit(State::Detected(0), s);
+        let severe = if self.asymptomatic[i] {
+            0.
+        } else {
+            sat_index(self.infected_severe_profile(i), t) * (1. - self.severe_protection[i])
+        };
+        let w = [sat_index(&self.config.infected_immune_profile, t), severe];
+        let s = Simulation::sample_state(&mut day.rng, &opts, &w);
+        Simulation::handle_r0(day, i, s);
+        day.counter.transit(State::Detected(0), s);
         s
     }
 
-    fn transit_unattended(&mut self) -> State {
-        let newstate = if self.hospitals_full() {
+    fn transit_unattended(&self, day: &mut Day) -> State {
+        let newstate = if self.hospitals_full(&day.counter) {
             State::Dead
         } else {
             State::Severe(1)
         };
-        self.counter.transit(State::Unattended, newstate);
+        day.counter.transit(State::Unattended, newstate);
         newstate
     }
 
-    fn transit_severe(&mut self, t: usize) -> State {
+    fn transit_severe(&self, day: &mut Day, t: usize, i: usize) -> State {
         let opts = [State::ImmuneDetected(0), State::Dead, State::Severe(t + 1)];
         let w = [
             sat_index(&self.config.severe_immune_profile, t),
-            sat_index(&self.config.severe_dead_profile, t),
+            sat_index(
+                self.config
+                    .severe_dead_profile_for_strain(self.strain_of(i), self.ages[i]),
+                t,
+            ),
         ];
-        let s = Simulation::sample_state(&opts, &w);
-        self.counter.transit(State::Severe(0), s);
+        let s = Simulation::sample_state(&mut day.rng, &opts, &w);
+        day.counter.transit(State::Severe(0), s);
         s
     }
 
-    fn transit_immune(&mut self, t: usize) -> State {
+    fn transit_immune(&self, day: &mut Day, t: usize) -> State {
         let opts = [State::Susceptible, State::Immune(t + 1)];
         let w = [sat_index(&self.config.immune_susceptible_profile, t)];
-        let s = Simulation::sample_state(&opts, &w);
-        self.counter.transit(State::Immune(0), s);
+        let s = Simulation::sample_state(&mut day.rng, &opts, &w);
+        day.counter.transit(State::Immune(0), s);
         s
     }
 
-    fn transit_immune_detected(&mut self, t: usize) -> State {
-        let opts = [State::Susceptible, State::ImmuneDetected(t + 1)];
-        let w = [sat_index(&self.config.immune_susceptible_profile, t)];
-        let s = Simulation::sample_state(&opts, &w);
-        self.counter.transit(State::ImmuneDetected(0), s);
+    fn transit_vaccinated(&self, day: &mut Day, t: usize) -> State {
+        let opts = [State::Susceptible, State::Vaccinated(t + 1)];
+        let w = [sat_index(&self.config.vaccinated_susceptible_profile, t)];
+        let s = Simulation::sample_state(&mut day.rng, &opts, &w);
+        day.counter.transit(State::Vaccinated(0), s);
         s
     }
 
-    fn get_family_contact_undetected_coef(&self) -> f64 {
-        self.config.family_contact_undetected_coef
-            * self
-                .family_contact_undetected_coef_mod
-                .iter()
-                .product::<f64>()
-    }
-
-    fn get_family_contact_detected_coef(&self) -> f64 {
-        self.config.family_contact_detected_coef
-            * self
-                .family_contact_detected_coef_mod
-                .iter()
-                .product::<f64>()
-    }
-
-    fn get_workplace_contact_undetected_coef(&self) -> f64 {
-        self.config.workplace_contact_undetected_coef
-            * self
-                .workplace_contact_undetected_coef_mod
-                .iter()
-                .product::<f64>()
-    }
-
-    fn get_workplace_contact_detected_coef(&self) -> f64 {
-        self.config.workplace_contact_detected_coef
-            * self
-                .workplace_contact_detected_coef_mod
-                .iter()
-                .product::<f64>()
-    }
-
-    fn get_world_contact_undetected_coef(&self) -> f64 {
-        self.config.world_contact_undetected_coef
-            * self
-                .world_contact_undetected_coef_mod
-                .iter()
-                .product::<f64>()
-    }
-
-    fn get_world_contact_detected_coef(&self) -> f64 {
-        self.config.world_contact_detected_coef
-            * self.world_contact_detected_coef_mod.iter().product::<f64>()
+    fn transit_immune_detected(&self, day: &mut Day, t: usize) -> State {
+        let opts = [State::Susceptible, State::ImmuneDetected(t + 1)];
+        let w = [sat_index(&self.config.immune_susceptible_profile, t)];
+        let s = Simulation::sample_state(&mut day.rng, &opts, &w);
+        day.counter.transit(State::ImmuneDetected(0), s);
+        s
     }
 }
 
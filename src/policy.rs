@@ -0,0 +1,446 @@
+use serde::de::{self, Deserializer};
+use serde::{Deserialize, Serialize};
+
+use crate::{Counter, Priority, Simulation, State};
+
+// The names and the fields match the policy forms of the web interface, so
+// that the same data can be fed to the browser and to native runs.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "policy", content = "data", rename_all = "kebab-case")]
+pub enum PolicyKind {
+    ShutWorkplaces {
+        workplaces: f64,
+    },
+    SocialDistancing {
+        #[serde(rename = "workplace-reduction")]
+        workplace_reduction: f64,
+        #[serde(rename = "world-reduction")]
+        world_reduction: f64,
+    },
+    Lockdown {
+        connections_cut_fraction: f64,
+    },
+    ContactTracing {
+        max_daily_tests: usize,
+    },
+    EnhancedSelfIsolation {
+        #[serde(rename = "household-reduction")]
+        household_reduction: f64,
+        #[serde(rename = "workplace-reduction")]
+        workplace_reduction: f64,
+        #[serde(rename = "world-reduction")]
+        world_reduction: f64,
+    },
+    Vaccination {
+        daily_doses: usize,
+        priority: Priority,
+    },
+    CloseSchools {
+        schools: f64,
+    },
+    // The policies below act on the contact layer with the given name.
+    ReduceTransmission {
+        layer: String,
+        undetected_reduction: f64,
+        detected_reduction: f64,
+    },
+    // Close a fraction of the groups of the layer, e.g. of its workplaces.
+    CloseGroups {
+        layer: String,
+        fraction: f64,
+    },
+    // Only for random layers.
+    CutConnections {
+        layer: String,
+        fraction: f64,
+    },
+}
+
+impl PolicyKind {
+    pub const NAMES: [&'static str; 10] = [
+        "shut-workplaces",
+        "social-distancing",
+        "lockdown",
+        "contact-tracing",
+        "enhanced-self-isolation",
+        "vaccination",
+        "close-schools",
+        "reduce-transmission",
+        "close-groups",
+        "cut-connections",
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PolicyKind::ShutWorkplaces { .. } => "shut-workplaces",
+            PolicyKind::SocialDistancing { .. } => "social-distancing",
+            PolicyKind::Lockdown { .. } => "lockdown",
+            PolicyKind::ContactTracing { .. } => "contact-tracing",
+            PolicyKind::EnhancedSelfIsolation { .. } => "enhanced-self-isolation",
+            PolicyKind::Vaccination { .. } => "vaccination",
+            PolicyKind::CloseSchools { .. } => "close-schools",
+            PolicyKind::ReduceTransmission { .. } => "reduce-transmission",
+            PolicyKind::CloseGroups { .. } => "close-groups",
+            PolicyKind::CutConnections { .. } => "cut-connections",
+        }
+    }
+
+    // The contact layer named by the policy, for the policies that take one.
+    pub fn layer(&self) -> Option<&str> {
+        match self {
+            PolicyKind::ReduceTransmission { layer, .. }
+            | PolicyKind::CloseGroups { layer, .. }
+            | PolicyKind::CutConnections { layer, .. } => Some(layer),
+            _ => None,
+        }
+    }
+
+    pub fn apply(&self, simulation: &mut Simulation) {
+        match self {
+            PolicyKind::ShutWorkplaces { workplaces } => {
+                simulation.close_fraction_of_groups("workplace", *workplaces);
+            }
+            PolicyKind::SocialDistancing {
+                workplace_reduction,
+                world_reduction,
+            } => {
+                simulation.multiply_undetected_infectability("workplace", 1. - workplace_reduction);
+                simulation.multiply_undetected_infectability("world", 1. - world_reduction);
+            }
+            PolicyKind::Lockdown {
+                connections_cut_fraction,
+            } => {
+                simulation.disable_fraction_of_connections("world", *connections_cut_fraction);
+            }
+            PolicyKind::ContactTracing { max_daily_tests } => {
+                simulation.set_max_contact_tracing(*max_daily_tests);
+            }
+            PolicyKind::EnhancedSelfIsolation {
+                household_reduction,
+                workplace_reduction,
+                world_reduction,
+            } => {
+                simulation.multiply_detected_infectability("family", 1. - household_reduction);
+                simulation.multiply_detected_infectability("workplace", 1. - workplace_reduction);
+                simulation.multiply_detected_infectability("world", 1. - world_reduction);
+            }
+            PolicyKind::Vaccination {
+                daily_doses,
+                priority,
+            } => {
+                simulation.start_vaccination(*daily_doses, *priority);
+            }
+            PolicyKind::CloseSchools { schools } => {
+                simulation.close_fraction_of_groups("school", *schools);
+            }
+            PolicyKind::ReduceTransmission {
+                layer,
+                undetected_reduction,
+                detected_reduction,
+            } => {
+                simulation.multiply_undetected_infectability(layer, 1. - undetected_reduction);
+                simulation.multiply_detected_infectability(layer, 1. - detected_reduction);
+            }
+            PolicyKind::CloseGroups { layer, fraction } => {
+                simulation.close_fraction_of_groups(layer, *fraction);
+            }
+            PolicyKind::CutConnections { layer, fraction } => {
+                simulation.disable_fraction_of_connections(layer, *fraction);
+            }
+        }
+    }
+
+    pub fn reverse(&self, simulation: &mut Simulation) {
+        match self {
+            PolicyKind::ShutWorkplaces { workplaces } => {
+                simulation.undo_close_fraction_of_groups("workplace", *workplaces);
+            }
+            PolicyKind::SocialDistancing {
+                workplace_reduction,
+                world_reduction,
+            } => {
+                simulation
+                    .undo_multiply_undetected_infectability("workplace", 1. - workplace_reduction);
+                simulation.undo_multiply_undetected_infectability("world", 1. - world_reduction);
+            }
+            PolicyKind::Lockdown {
+                connections_cut_fraction,
+            } => {
+                simulation
+                    .undo_disable_fraction_of_connections("world", *connections_cut_fraction);
+            }
+            PolicyKind::ContactTracing { max_daily_tests } => {
+                simulation.undo_set_max_contact_tracing(*max_daily_tests);
+            }
+            PolicyKind::EnhancedSelfIsolation {
+                household_reduction,
+                workplace_reduction,
+                world_reduction,
+            } => {
+                simulation.undo_multiply_detected_infectability("family", 1. - household_reduction);
+                simulation
+                    .undo_multiply_detected_infectability("workplace", 1. - workplace_reduction);
+                simulation.undo_multiply_detected_infectability("world", 1. - world_reduction);
+            }
+            PolicyKind::Vaccination {
+                daily_doses,
+                priority,
+            } => {
+                simulation.undo_start_vaccination(*daily_doses, *priority);
+            }
+            PolicyKind::CloseSchools { schools } => {
+                simulation.undo_close_fraction_of_groups("school", *schools);
+            }
+            PolicyKind::ReduceTransmission {
+                layer,
+                undetected_reduction,
+                detected_reduction,
+            } => {
+                simulation.undo_multiply_undetected_infectability(layer, 1. - undetected_reduction);
+                simulation.undo_multiply_detected_infectability(layer, 1. - detected_reduction);
+            }
+            PolicyKind::CloseGroups { layer, fraction } => {
+                simulation.undo_close_fraction_of_groups(layer, *fraction);
+            }
+            PolicyKind::CutConnections { layer, fraction } => {
+                simulation.undo_disable_fraction_of_connections(layer, *fraction);
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum Variable {
+    #[serde(rename = "time")]
+    Time,
+    // Days since the policy was applied. Only meaningful for shutdowns.
+    #[serde(rename = "duration")]
+    Duration,
+    // Never happens. Only meaningful for shutdowns.
+    #[serde(rename = "permanent")]
+    Permanent,
+    Susceptible,
+    Exposed,
+    #[serde(rename = "Infected (Undetected)")]
+    Infected,
+    Asymptomatic,
+    #[serde(rename = "Infected (Detected)")]
+    Detected,
+    Severe,
+    Unattended,
+    // Both detected and undetected.
+    Immune,
+    #[serde(rename = "Immune (Undetected)")]
+    ImmuneUndetected,
+    #[serde(rename = "Immune (Detected)")]
+    ImmuneDetected,
+    Dead,
+    #[serde(rename = "Partially vaccinated")]
+    PartiallyVaccinated,
+    Vaccinated,
+}
+
+impl Variable {
+    fn value(self, time: usize, applied_at: usize, counter: &Counter) -> Option<f64> {
+        let count = |s| counter.state_count(s) as f64;
+        let res = match self {
+            Variable::Time => time as f64,
+            Variable::Duration => (time - applied_at) as f64,
+            Variable::Permanent => return None,
+            Variable::Susceptible => count(State::Susceptible),
+            Variable::Exposed => count(State::Exposed(0)),
+            Variable::Infected => count(State::Infected(0)),
+            Variable::Asymptomatic => count(State::Asymptomatic(0)),
+            Variable::Detected => count(State::Detected(0)),
+            Variable::Severe => count(State::Severe(0)),
+            Variable::Unattended => count(State::Unattended),
+            Variable::Immune => count(State::Immune(0)) + count(State::ImmuneDetected(0)),
+            Variable::ImmuneUndetected => count(State::Immune(0)),
+            Variable::ImmuneDetected => count(State::ImmuneDetected(0)),
+            Variable::Dead => count(State::Dead),
+            Variable::PartiallyVaccinated => count(State::PartiallyVaccinated(0)),
+            Variable::Vaccinated => count(State::Vaccinated(0)),
+        };
+        Some(res)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum Operator {
+    #[serde(rename = ">=")]
+    GreaterEqual,
+    #[serde(rename = "<=")]
+    LessEqual,
+    #[serde(rename = "==")]
+    Equal,
+}
+
+impl Operator {
+    fn compare(self, a: f64, b: f64) -> bool {
+        match self {
+            Operator::GreaterEqual => a >= b,
+            Operator::LessEqual => a <= b,
+            Operator::Equal => a == b,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Trigger {
+    #[serde(rename = "trigger-variable")]
+    pub variable: Variable,
+    // The web interface doesn't send an operator for permanent shutdowns.
+    #[serde(rename = "trigger-operator", default)]
+    pub operator: Option<Operator>,
+    #[serde(rename = "trigger-value", default)]
+    pub value: f64,
+}
+
+impl Trigger {
+    fn check(&self, time: usize, applied_at: usize, counter: &Counter) -> bool {
+        match (
+            self.operator,
+            self.variable.value(time, applied_at, counter),
+        ) {
+            (Some(op), Some(v)) => op.compare(v, self.value),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Shutdown {
+    #[serde(flatten)]
+    pub trigger: Trigger,
+    // Schedule the policy again once it has been reversed.
+    #[serde(default)]
+    pub recurrent: bool,
+}
+
+// A policy as specified in the web interface: it is applied when the trigger
+// condition is met and reversed when the shutdown condition is met.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Policy {
+    #[serde(flatten)]
+    pub kind: PolicyKind,
+    pub trigger: Trigger,
+    pub shutdown: Shutdown,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PolicyEventKind {
+    Applied,
+    Reversed,
+}
+
+// Policy names are static strings, so they are deserialized by looking them
+// up in the list of names. The alias keeps serde from trying to borrow them
+// from the input.
+type StaticName = &'static str;
+
+fn deserialize_name<'de, D: Deserializer<'de>>(deserializer: D) -> Result<&'static str, D::Error> {
+    let name = String::deserialize(deserializer)?;
+    PolicyKind::NAMES
+        .iter()
+        .find(|n| **n == name)
+        .copied()
+        .ok_or_else(|| de::Error::unknown_variant(&name, &PolicyKind::NAMES))
+}
+
+pub(crate) fn deserialize_names<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Vec<&'static str>>, D::Error> {
+    #[derive(Deserialize)]
+    struct Name(#[serde(deserialize_with = "deserialize_name")] StaticName);
+    let names: Option<Vec<Name>> = Option::deserialize(deserializer)?;
+    Ok(names.map(|names| names.into_iter().map(|n| n.0).collect()))
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PolicyEvent {
+    pub time: usize,
+    #[serde(deserialize_with = "deserialize_name")]
+    pub policy: StaticName,
+    pub event: PolicyEventKind,
+}
+
+#[derive(Default, Serialize, Deserialize, Clone)]
+pub(crate) struct PolicyEngine {
+    pending: Vec<Policy>,
+    // Active policies together with the time at which they were applied.
+    active: Vec<(Policy, usize)>,
+    // Policies applied with a permanent shutdown condition.
+    permanent: Vec<Policy>,
+    events: Vec<PolicyEvent>,
+}
+
+impl PolicyEngine {
+    pub(crate) fn add(&mut self, policy: Policy) {
+        self.pending.push(policy);
+    }
+
+    pub(crate) fn events(&self) -> &[PolicyEvent] {
+        &self.events
+    }
+
+    // Names of the policies in effect, once per application.
+    pub(crate) fn in_effect(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.permanent
+            .iter()
+            .chain(self.active.iter().map(|(p, _)| p))
+            .map(|p| p.kind.name())
+    }
+
+    // Apply the policies whose trigger condition is met and then reverse the
+    // active ones whose shutdown condition is met. A policy can be applied
+    // and reversed in the same step.
+    pub(crate) fn run(simulation: &mut Simulation) {
+        let mut engine = std::mem::take(&mut simulation.policies);
+        engine.events.clear();
+        let time = simulation.get_time();
+
+        let mut pending = Vec::with_capacity(engine.pending.len());
+        for p in engine.pending.drain(..) {
+            if p.trigger.check(time, time, simulation.counter()) {
+                p.kind.apply(simulation);
+                engine.events.push(PolicyEvent {
+                    time,
+                    policy: p.kind.name(),
+                    event: PolicyEventKind::Applied,
+                });
+                if p.shutdown.trigger.variable != Variable::Permanent {
+                    engine.active.push((p, time));
+                } else {
+                    engine.permanent.push(p);
+                }
+            } else {
+                pending.push(p);
+            }
+        }
+        engine.pending = pending;
+
+        let mut active = Vec::with_capacity(engine.active.len());
+        for (p, applied_at) in engine.active.drain(..) {
+            if p.shutdown
+                .trigger
+                .check(time, applied_at, simulation.counter())
+            {
+                p.kind.reverse(simulation);
+                engine.events.push(PolicyEvent {
+                    time,
+                    policy: p.kind.name(),
+                    event: PolicyEventKind::Reversed,
+                });
+                if p.shutdown.recurrent {
+                    engine.pending.push(p);
+                }
+            } else {
+                active.push((p, applied_at));
+            }
+        }
+        engine.active = active;
+
+        simulation.policies = engine;
+    }
+}
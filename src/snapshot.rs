@@ -0,0 +1,130 @@
+use std::convert::TryInto;
+use std::error::Error;
+use std::fmt;
+
+use gloo_utils::format::JsValueSerdeExt;
+use wasm_bindgen::prelude::*;
+
+use crate::validate::ErrorMessage;
+use crate::{utils, Simulation, NSTATES};
+
+// Written at the start of every snapshot. Increase it whenever the state of
+// the simulation changes, so that older snapshots are rejected instead of
+// being restored wrongly.
+const SNAPSHOT_VERSION: u32 = 7;
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    // The snapshot was written by a different version of the simulation.
+    Version(u32),
+    Invalid(String),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SnapshotError::Version(v) => write!(
+                f,
+                "Snapshot version {} is not supported, expected {}",
+                v, SNAPSHOT_VERSION
+            ),
+            SnapshotError::Invalid(message) => write!(f, "Invalid snapshot: {}", message),
+        }
+    }
+}
+
+impl Error for SnapshotError {}
+
+impl Simulation {
+    // Restore a simulation saved with `to_bytes`. It continues exactly like
+    // the original one, including the random numbers.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Simulation, SnapshotError> {
+        utils::set_panic_hook();
+        if bytes.len() < 4 {
+            return Err(SnapshotError::Invalid("too short".to_string()));
+        }
+        let (version, body) = bytes.split_at(4);
+        let version = u32::from_le_bytes(version.try_into().unwrap());
+        if version != SNAPSHOT_VERSION {
+            return Err(SnapshotError::Version(version));
+        }
+        let simulation: Simulation =
+            rmp_serde::from_slice(body).map_err(|e| SnapshotError::Invalid(e.to_string()))?;
+        simulation.check().map_err(SnapshotError::Invalid)?;
+        Ok(simulation)
+    }
+
+    // The checks that a snapshot that decodes can still fail: the sizes that
+    // the simulation relies on without checking them on every tick.
+    fn check(&self) -> Result<(), String> {
+        let n = self.states.len();
+        for layer in self.layers.iter() {
+            let graph = &layer.graph;
+            if graph.offsets.len() != n + 1
+                || graph.offsets.windows(2).any(|w| w[0] > w[1])
+                || graph.offsets[n] != graph.neighbours.len()
+                || graph.neighbours.iter().any(|j| *j as usize >= n)
+                || !(graph.weights.is_empty() || graph.weights.len() == graph.neighbours.len())
+            {
+                return Err(format!(
+                    "the graph of {} doesn't match the agents",
+                    layer.spec.name
+                ));
+            }
+        }
+        if self.asymptomatic.len() != n
+            || self.ages.len() != n
+            || self.severe_protection.len() != n
+            || self.strain.len() != n
+            || self.infections_caused.len() != n
+        {
+            return Err("the agents don't match".to_string());
+        }
+        if self.ages.iter().any(|a| *a >= self.age_counters.len())
+            || self
+                .strain
+                .iter()
+                .any(|s| s.is_some_and(|s| s >= self.strain_counters.len()))
+        {
+            return Err("unknown age band or strain".to_string());
+        }
+        let mut total = [0; NSTATES];
+        let mut by_age = vec![[0; NSTATES]; self.age_counters.len()];
+        for (s, a) in self.states.iter().zip(self.ages.iter()) {
+            total[s.index()] += 1;
+            by_age[*a][s.index()] += 1;
+        }
+        if self.counter.abs_counter != total
+            || self
+                .age_counters
+                .iter()
+                .zip(by_age.iter())
+                .any(|(c, t)| c.abs_counter != *t)
+        {
+            return Err("the counters don't match the states".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[wasm_bindgen]
+impl Simulation {
+    // The full state of the simulation: the version of the format as a
+    // little endian u32 followed by the simulation in MessagePack.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = SNAPSHOT_VERSION.to_le_bytes().to_vec();
+        rmp_serde::encode::write_named(&mut bytes, self).unwrap();
+        bytes
+    }
+
+    #[wasm_bindgen(js_name = from_bytes)]
+    pub fn from_bytes_js(bytes: &[u8]) -> Result<Simulation, JsValue> {
+        Simulation::from_bytes(bytes).map_err(|e| {
+            let errors = vec![ErrorMessage {
+                field: None,
+                message: e.to_string(),
+            }];
+            <JsValue as JsValueSerdeExt>::from_serde(&errors).unwrap()
+        })
+    }
+}
@@ -0,0 +1,154 @@
+use std::collections::BTreeMap;
+
+use gloo_utils::format::JsValueSerdeExt;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+use crate::{Graph, Simulation};
+
+// The structure of a contact layer, over the agents that belong to it and
+// the links between them that it has now. The connections cut in the random layers are
+// gone, but the links of the closed groups are still counted, as closing a
+// group only stops transmission through them. The averages are NaN for the
+// layers without agents.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LayerStats {
+    pub name: String,
+    pub nodes: usize,
+    pub edges: usize,
+    // The number of nodes with each degree.
+    pub degree_distribution: Vec<usize>,
+    pub mean_degree: f64,
+    pub degree_variance: f64,
+    // The average of the local clustering coefficients, counting as 0 the
+    // nodes with fewer than two neighbours.
+    pub clustering: f64,
+    // The number of connected components of each size.
+    pub component_sizes: BTreeMap<usize, usize>,
+    pub largest_component_fraction: f64,
+    // The number of groups of each size, e.g. of workplaces, for the layers
+    // with groups. The groups that ended up empty have size 0.
+    pub group_sizes: Option<BTreeMap<usize, usize>>,
+}
+
+fn histogram(values: impl Iterator<Item = usize>) -> BTreeMap<usize, usize> {
+    let mut res = BTreeMap::new();
+    for v in values {
+        *res.entry(v).or_insert(0) += 1;
+    }
+    res
+}
+
+// The fraction of the pairs of neighbours of `i` that are linked, using
+// `marked` as scratch space.
+fn local_clustering(graph: &Graph, i: usize, marked: &mut [bool]) -> f64 {
+    let degree = graph.iternodes(i).count();
+    if degree < 2 {
+        return 0.;
+    }
+    for j in graph.iternodes(i) {
+        marked[j] = true;
+    }
+    // Each link between neighbours is seen from both ends.
+    let mut links = 0;
+    for j in graph.iternodes(i) {
+        links += graph.iternodes(j).filter(|k| marked[*k]).count();
+    }
+    for j in graph.iternodes(i) {
+        marked[j] = false;
+    }
+    links as f64 / (degree * (degree - 1)) as f64
+}
+
+// The sizes of the connected components of the members.
+fn components(graph: &Graph, members: &[bool]) -> Vec<usize> {
+    let mut seen = vec![false; members.len()];
+    let mut sizes = Vec::new();
+    let mut stack = Vec::new();
+    for start in 0..members.len() {
+        if !members[start] || seen[start] {
+            continue;
+        }
+        seen[start] = true;
+        stack.push(start);
+        let mut size = 0;
+        while let Some(i) = stack.pop() {
+            size += 1;
+            for j in graph.iternodes(i) {
+                if members[j] && !seen[j] {
+                    seen[j] = true;
+                    stack.push(j);
+                }
+            }
+        }
+        sizes.push(size);
+    }
+    sizes
+}
+
+impl Simulation {
+    // The statistics of every contact layer, in the order of `layer_names`.
+    pub fn network_stats(&self) -> Vec<LayerStats> {
+        let mut marked = vec![false; self.states.len()];
+        self.layers
+            .iter()
+            .map(|layer| {
+                let graph = &layer.graph;
+                let members = layer.members(&self.config, &self.ages);
+                let nodes: Vec<usize> = (0..members.len()).filter(|i| members[*i]).collect();
+                // Only the links between members count.
+                let degrees: Vec<usize> = nodes
+                    .iter()
+                    .map(|i| graph.iternodes(*i).filter(|j| members[*j]).count())
+                    .collect();
+                let n = nodes.len() as f64;
+                let mut degree_distribution = vec![0; degrees.iter().max().map_or(0, |d| d + 1)];
+                for d in degrees.iter() {
+                    degree_distribution[*d] += 1;
+                }
+                let mean_degree = degrees.iter().sum::<usize>() as f64 / n;
+                let degree_variance = degrees
+                    .iter()
+                    .map(|d| (*d as f64 - mean_degree).powi(2))
+                    .sum::<f64>()
+                    / n;
+                let clustering = nodes
+                    .iter()
+                    .map(|i| local_clustering(graph, *i, &mut marked))
+                    .sum::<f64>()
+                    / n;
+                let components = components(graph, &members);
+                let largest = components.iter().max().copied().unwrap_or(0);
+                let group_sizes = if layer.groups.is_empty() {
+                    None
+                } else {
+                    let mut sizes = vec![0; layer.ngroups];
+                    for g in layer.groups.iter().flatten() {
+                        sizes[*g] += 1;
+                    }
+                    Some(histogram(sizes.into_iter()))
+                };
+                LayerStats {
+                    name: layer.spec.name.clone(),
+                    nodes: nodes.len(),
+                    edges: degrees.iter().sum::<usize>() / 2,
+                    degree_distribution,
+                    mean_degree,
+                    degree_variance,
+                    clustering,
+                    component_sizes: histogram(components.into_iter()),
+                    largest_component_fraction: largest as f64 / n,
+                    group_sizes,
+                }
+            })
+            .collect()
+    }
+}
+
+#[wasm_bindgen]
+impl Simulation {
+    #[wasm_bindgen(js_name = network_stats)]
+    pub fn network_stats_js(&self) -> JsValue {
+        <JsValue as JsValueSerdeExt>::from_serde(&self.network_stats()).unwrap()
+    }
+}
@@ -0,0 +1,232 @@
+use rand::Rng;
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::day::NewStates;
+use crate::validate::{Reason, Validator};
+use crate::{Config, Counter, Simulation, State};
+
+// The name of the strain described by the profiles at the top level of the
+// configuration.
+pub const ORIGINAL_STRAIN: &str = "original";
+
+// A strain that appears during the epidemic. The profiles that are not given
+// are the same as for the original strain. A strain profile is used for every
+// age band, instead of the per age ones.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Strain {
+    pub name: String,
+    // Day at which `seed_size` Susceptible agents become infected with it.
+    // The first day is 1, as the day 0 is the initial state.
+    pub seed_day: usize,
+    pub seed_size: usize,
+    #[serde(default)]
+    pub susceptible_infected_profile: Option<Vec<f64>>,
+    #[serde(default)]
+    pub infected_detected_profile: Option<Vec<f64>>,
+    #[serde(default)]
+    pub infected_severe_profile: Option<Vec<f64>>,
+    #[serde(default)]
+    pub severe_dead_profile: Option<Vec<f64>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StrainCounter {
+    pub strain: String,
+    #[serde(flatten)]
+    pub counter: Counter,
+}
+
+impl Config {
+    pub(crate) fn nstrains(&self) -> usize {
+        self.strains.len() + 1
+    }
+
+    fn strain_profile(
+        &self,
+        strain: usize,
+        field: fn(&Strain) -> &Option<Vec<f64>>,
+    ) -> Option<&[f64]> {
+        if strain == 0 {
+            return None;
+        }
+        field(&self.strains[strain - 1]).as_deref()
+    }
+
+    pub(crate) fn susceptible_infected_profile_for(&self, strain: usize) -> &[f64] {
+        self.strain_profile(strain, |s| &s.susceptible_infected_profile)
+            .unwrap_or(&self.susceptible_infected_profile)
+    }
+
+    pub(crate) fn infected_detected_profile_for(&self, strain: usize) -> &[f64] {
+        self.strain_profile(strain, |s| &s.infected_detected_profile)
+            .unwrap_or(&self.infected_detected_profile)
+    }
+
+    pub(crate) fn infected_severe_profile_for_strain(&self, strain: usize, band: usize) -> &[f64] {
+        self.strain_profile(strain, |s| &s.infected_severe_profile)
+            .unwrap_or_else(|| self.infected_severe_profile_for(band))
+    }
+
+    pub(crate) fn severe_dead_profile_for_strain(&self, strain: usize, band: usize) -> &[f64] {
+        self.strain_profile(strain, |s| &s.severe_dead_profile)
+            .unwrap_or_else(|| self.severe_dead_profile_for(band))
+    }
+
+    // Whether Immune agents can be infected again.
+    pub(crate) fn reinfections(&self) -> bool {
+        self.cross_immunity.iter().flatten().any(|x| *x < 1.)
+    }
+
+    // Protection against infection by `strain` given by a previous infection
+    // by `previous`.
+    pub(crate) fn cross_immunity(&self, previous: usize, strain: usize) -> f64 {
+        self.cross_immunity
+            .get(previous)
+            .and_then(|row| row.get(strain))
+            .copied()
+            .unwrap_or(1.)
+    }
+
+    pub(crate) fn validate_strains(&self, v: &mut Validator) {
+        for s in self.strains.iter() {
+            for (field, value) in [
+                ("strains.seed_day", s.seed_day),
+                ("strains.seed_size", s.seed_size),
+            ]
+            .iter()
+            {
+                if *value == 0 {
+                    v.push(field, Reason::NotPositive { value: 0. });
+                }
+            }
+            for (field, profile) in [
+                (
+                    "strains.susceptible_infected_profile",
+                    &s.susceptible_infected_profile,
+                ),
+                (
+                    "strains.infected_detected_profile",
+                    &s.infected_detected_profile,
+                ),
+                ("strains.infected_severe_profile", &s.infected_severe_profile),
+                ("strains.severe_dead_profile", &s.severe_dead_profile),
+            ]
+            .iter()
+            {
+                if let Some(profile) = profile {
+                    v.profile(field, profile);
+                }
+            }
+        }
+        let n = self.nstrains();
+        if !self.cross_immunity.is_empty()
+            && (self.cross_immunity.len() != n || self.cross_immunity.iter().any(|r| r.len() != n))
+        {
+            v.push("cross_immunity", Reason::LengthMismatch { other: "strains" });
+        }
+        for x in self.cross_immunity.iter().flatten() {
+            v.probability("cross_immunity", *x);
+        }
+    }
+}
+
+impl Simulation {
+    pub fn counter_by_strain(&self) -> Vec<StrainCounter> {
+        let names =
+            std::iter::once(ORIGINAL_STRAIN).chain(self.config.strains.iter().map(|s| &s.name[..]));
+        names
+            .zip(self.strain_counters.iter())
+            .map(|(name, counter)| StrainCounter {
+                strain: name.to_string(),
+                counter: counter.clone(),
+            })
+            .collect()
+    }
+
+    // The strain of the current or last infection of an agent.
+    pub(crate) fn strain_of(&self, i: usize) -> usize {
+        self.strain[i].unwrap_or(0)
+    }
+
+    pub(crate) fn infected_severe_profile(&self, i: usize) -> &[f64] {
+        self.config
+            .infected_severe_profile_for_strain(self.strain_of(i), self.ages[i])
+    }
+
+    pub(crate) fn set_strain(&mut self, i: usize, strain: usize) {
+        if self.strain[i] != Some(strain) {
+            self.strain_changes.push((i, self.strain[i]));
+            self.strain[i] = Some(strain);
+        }
+    }
+
+    // Update the per strain counters with the transitions of today, given as
+    // the agents whose state changed, in order, with their old and new state.
+    // Only the agents that have been infected at some point are counted,
+    // under the strain of their last infection.
+    pub(crate) fn update_strain_counters(&mut self, changes: &[(usize, State, State)]) {
+        // An agent only changes strain when it is infected, so it is in the
+        // changes.
+        let previous: FxHashMap<usize, Option<usize>> = self.strain_changes.drain(..).collect();
+        for (i, old, new) in changes.iter().copied() {
+            let strain = match self.strain[i] {
+                Some(s) => s,
+                None => continue,
+            };
+            match previous.get(&i) {
+                Some(prev) => {
+                    if let Some(prev) = prev {
+                        self.strain_counters[*prev].unregister(old);
+                    }
+                    self.strain_counters[strain].register(new);
+                    self.strain_counters[strain].day_counter[new.index()] += 1;
+                }
+                None => self.strain_counters[strain].transit(old, new),
+            }
+        }
+    }
+
+    fn seed_agent(&mut self, newstates: &mut NewStates, i: usize, strain: usize) {
+        self.set_state(newstates, i, State::Infected(0));
+        self.set_strain(i, strain);
+        self.log_seed(i);
+        self.asymptomatic[i] = false;
+        self.severe_protection[i] = 0.;
+        self.seeded_today += 1;
+    }
+
+    // Infect the initial agents of the strains that appear today.
+    pub(crate) fn seed_strains(&mut self, newstates: &mut NewStates) {
+        let today = self.time + 1;
+        let nagents = self.states.len();
+        for strain in 1..self.config.nstrains() {
+            let s = &self.config.strains[strain - 1];
+            if s.seed_day != today {
+                continue;
+            }
+            let susceptible = self.counter.state_count(State::Susceptible) as usize;
+            let n = usize::min(s.seed_size, susceptible);
+            if 2 * n <= susceptible {
+                // Draw agents until enough of them are Susceptible, which
+                // takes fewer than 2 * nagents / susceptible draws each on
+                // average.
+                let mut seeded = 0;
+                while seeded < n {
+                    let i = self.rng.gen_range(0, nagents);
+                    if let State::Susceptible = newstates.get(&self.states, i) {
+                        self.seed_agent(newstates, i, strain);
+                        seeded += 1;
+                    }
+                }
+            } else {
+                let susceptible: Vec<usize> = (0..nagents)
+                    .filter(|i| matches!(newstates.get(&self.states, *i), State::Susceptible))
+                    .collect();
+                for j in rand::seq::index::sample(&mut self.rng, susceptible.len(), n).iter() {
+                    self.seed_agent(newstates, susceptible[j], strain);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,312 @@
+use std::error::Error;
+use std::fmt;
+
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::{Config, ConfigError, Policy, Simulation, State};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Values {
+    // An explicit list of values.
+    Values(Vec<Value>),
+    // `steps` equally spaced values from `from` to `to`, both included. The
+    // Latin hypercube sampling takes any value in the interval and ignores
+    // `steps`.
+    Range {
+        from: f64,
+        to: f64,
+        #[serde(default)]
+        steps: usize,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Parameter {
+    // Dot separated path of the configuration field, where numbers index lists,
+    // e.g. `hospital_capacity` or `family_size_weights.0`.
+    pub field: String,
+    #[serde(flatten)]
+    pub values: Values,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum Sampling {
+    // Every combination of the values of every parameter.
+    #[default]
+    Grid,
+    // `samples` points, such that each of the `samples` equally sized
+    // intervals of every parameter is visited exactly once.
+    LatinHypercube {
+        samples: usize,
+    },
+}
+
+fn default_seeds() -> usize {
+    1
+}
+
+fn default_days() -> usize {
+    365
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SweepSpec {
+    pub parameters: Vec<Parameter>,
+    #[serde(default)]
+    pub sampling: Sampling,
+    // Number of runs with different seeds for each variant.
+    #[serde(default = "default_seeds")]
+    pub seeds: usize,
+    // Maximum number of days of each run.
+    #[serde(default = "default_days")]
+    pub days: usize,
+}
+
+#[derive(Debug)]
+pub enum SweepError {
+    UnknownField(String),
+    NoValues(String),
+    InvalidValue { field: String, message: String },
+    InvalidConfig(Vec<ConfigError>),
+}
+
+impl fmt::Display for SweepError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SweepError::UnknownField(field) => write!(f, "Unknown configuration field {}", field),
+            SweepError::NoValues(field) => write!(f, "No values given for {}", field),
+            SweepError::InvalidValue { field, message } => {
+                write!(f, "Invalid value for {}: {}", field, message)
+            }
+            SweepError::InvalidConfig(errors) => {
+                let messages: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+                write!(f, "Invalid configuration: {}", messages.join("; "))
+            }
+        }
+    }
+}
+
+impl Error for SweepError {}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Outcome {
+    pub days: usize,
+    pub total_deaths: i32,
+    pub peak_severe: i32,
+    pub peak_severe_day: usize,
+    // Peak of Exposed, Infected, Asymptomatic and Detected.
+    pub peak_infected: i32,
+    pub peak_infected_day: usize,
+    // Infections per agent, counting reinfections.
+    pub attack_rate: f64,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct SweepRow {
+    pub variant: usize,
+    pub seed: u64,
+    pub parameters: Map<String, Value>,
+    #[serde(flatten)]
+    pub outcome: Outcome,
+}
+
+fn set_path(config: &mut Value, path: &str, new: Value) -> Result<(), SweepError> {
+    let mut v = config;
+    for key in path.split('.') {
+        let next = match v {
+            Value::Object(m) => m.get_mut(key),
+            Value::Array(a) => key.parse::<usize>().ok().and_then(move |i| a.get_mut(i)),
+            _ => None,
+        };
+        v = next.ok_or_else(|| SweepError::UnknownField(path.to_string()))?;
+    }
+    // Values computed from ranges are floats, but some fields are integers.
+    *v = match (&*v, new.as_f64()) {
+        (Value::Number(n), Some(x)) if n.is_u64() || n.is_i64() => Value::from(x.round() as i64),
+        _ => new,
+    };
+    Ok(())
+}
+
+impl Values {
+    fn len(&self) -> usize {
+        match self {
+            Values::Values(v) => v.len(),
+            Values::Range { steps, .. } => *steps,
+        }
+    }
+
+    fn get(&self, i: usize) -> Value {
+        match self {
+            Values::Values(v) => v[i].clone(),
+            Values::Range { from, to, steps } => {
+                if *steps == 1 {
+                    Value::from(*from)
+                } else {
+                    Value::from(from + (to - from) * (i as f64) / ((steps - 1) as f64))
+                }
+            }
+        }
+    }
+
+    // A value at the relative position `x` in [0, 1).
+    fn interpolate(&self, x: f64) -> Value {
+        match self {
+            Values::Values(v) => v[((x * v.len() as f64) as usize).min(v.len() - 1)].clone(),
+            Values::Range { from, to, .. } => Value::from(from + (to - from) * x),
+        }
+    }
+}
+
+impl SweepSpec {
+    // The values of the parameters for each variant.
+    pub fn variants<R: Rng>(&self, rng: &mut R) -> Result<Vec<Vec<Value>>, SweepError> {
+        for p in self.parameters.iter() {
+            let empty = match (&self.sampling, &p.values) {
+                (Sampling::LatinHypercube { .. }, Values::Range { .. }) => false,
+                (_, values) => values.len() == 0,
+            };
+            if empty {
+                return Err(SweepError::NoValues(p.field.clone()));
+            }
+        }
+        let res = match self.sampling {
+            Sampling::Grid => {
+                let mut res = vec![Vec::new()];
+                for p in self.parameters.iter() {
+                    res = res
+                        .into_iter()
+                        .flat_map(|prefix| {
+                            (0..p.values.len()).map(move |i| {
+                                let mut v = prefix.clone();
+                                v.push(p.values.get(i));
+                                v
+                            })
+                        })
+                        .collect();
+                }
+                res
+            }
+            Sampling::LatinHypercube { samples } => {
+                let mut res = vec![Vec::with_capacity(self.parameters.len()); samples];
+                for p in self.parameters.iter() {
+                    let mut strata: Vec<usize> = (0..samples).collect();
+                    strata.shuffle(rng);
+                    for (row, stratum) in res.iter_mut().zip(strata) {
+                        let x = (stratum as f64 + rng.gen::<f64>()) / samples as f64;
+                        row.push(p.values.interpolate(x));
+                    }
+                }
+                res
+            }
+        };
+        Ok(res)
+    }
+}
+
+fn run_variant(config: Config, policies: &[Policy], days: usize) -> Outcome {
+    let mut simulation = Simulation::new(config);
+    // The policies have been validated with the configuration.
+    for p in policies {
+        simulation.policies.add(p.clone());
+    }
+    let count = |s: &Simulation, state| s.counter.state_count(state);
+    let population: i32 = simulation.counter.abs_counter.iter().sum();
+    let mut infections = count(&simulation, State::Infected(0));
+    let mut outcome = Outcome {
+        days: 0,
+        total_deaths: 0,
+        peak_severe: 0,
+        peak_severe_day: 0,
+        peak_infected: simulation.counter.active_infections(),
+        peak_infected_day: 0,
+        attack_rate: 0.,
+    };
+    while simulation.time < days {
+        let ongoing = simulation.counter.active_infections()
+            + count(&simulation, State::Severe(0))
+            + count(&simulation, State::Unattended);
+        if ongoing == 0 {
+            break;
+        }
+        simulation.tick();
+        let time = simulation.time;
+        infections += simulation.new_infections();
+        let severe = count(&simulation, State::Severe(0));
+        if severe > outcome.peak_severe {
+            outcome.peak_severe = severe;
+            outcome.peak_severe_day = time;
+        }
+        let infected = simulation.counter.active_infections();
+        if infected > outcome.peak_infected {
+            outcome.peak_infected = infected;
+            outcome.peak_infected_day = time;
+        }
+    }
+    outcome.days = simulation.time;
+    outcome.total_deaths = count(&simulation, State::Dead);
+    outcome.attack_rate = infections as f64 / population as f64;
+    outcome
+}
+
+// Run every variant of the base configuration given by the sweep
+// specification `spec.seeds` times and collect the outcomes.
+pub fn run_sweep(
+    base: &Config,
+    policies: &[Policy],
+    spec: &SweepSpec,
+) -> Result<Vec<SweepRow>, SweepError> {
+    let mut rng = base.rng();
+    let base_value = serde_json::to_value(base).unwrap();
+    let mut configs = Vec::new();
+    for values in spec.variants(&mut rng)? {
+        let mut value = base_value.clone();
+        let mut parameters = Map::new();
+        for (p, v) in spec.parameters.iter().zip(values) {
+            set_path(&mut value, &p.field, v)?;
+            parameters.insert(p.field.clone(), value_at(&value, &p.field));
+        }
+        let config: Config =
+            serde_json::from_value(value).map_err(|e| SweepError::InvalidValue {
+                field: spec
+                    .parameters
+                    .iter()
+                    .map(|p| p.field.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                message: e.to_string(),
+            })?;
+        let mut errors = config.validate();
+        errors.extend(config.validate_policies(policies));
+        if !errors.is_empty() {
+            return Err(SweepError::InvalidConfig(errors));
+        }
+        configs.push((config, parameters));
+    }
+
+    let mut res = Vec::with_capacity(configs.len() * spec.seeds);
+    for (variant, (config, parameters)) in configs.into_iter().enumerate() {
+        for _ in 0..spec.seeds {
+            let seed = rng.gen();
+            let mut c = config.clone();
+            c.seed = Some(seed);
+            res.push(SweepRow {
+                variant,
+                seed,
+                parameters: parameters.clone(),
+                outcome: run_variant(c, policies, spec.days),
+            });
+        }
+    }
+    Ok(res)
+}
+
+fn value_at(config: &Value, path: &str) -> Value {
+    let pointer = format!("/{}", path.replace('.', "/"));
+    config.pointer(&pointer).cloned().unwrap_or(Value::Null)
+}
@@ -0,0 +1,237 @@
+use std::fmt::Write as _;
+use std::io::{self, Write};
+
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::day::Infection;
+use crate::{Simulation, State};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct InfectionEvent {
+    pub day: usize,
+    pub infectee: usize,
+    // The infector fields are empty for the infections that don't come from
+    // a contact: the ones present when the recording starts and the seeds of
+    // new strains.
+    pub infector: Option<usize>,
+    // The name of the contact layer.
+    pub network: Option<String>,
+    pub infector_detected: bool,
+    // Days since the infector became infectious.
+    pub infector_t: Option<usize>,
+}
+
+impl InfectionEvent {
+    fn root(day: usize, infectee: usize) -> Self {
+        InfectionEvent {
+            day,
+            infectee,
+            infector: None,
+            network: None,
+            infector_detected: false,
+            infector_t: None,
+        }
+    }
+}
+
+// The infection events arranged as a forest: the parent of an infection is
+// the last infection of the infector.
+pub struct TransmissionTree {
+    events: Vec<InfectionEvent>,
+    children: Vec<Vec<usize>>,
+    roots: Vec<usize>,
+}
+
+impl TransmissionTree {
+    pub fn new(events: &[InfectionEvent]) -> Self {
+        let mut last_infection: FxHashMap<usize, usize> = Default::default();
+        let mut children: Vec<Vec<usize>> = vec![Vec::new(); events.len()];
+        let mut roots = Vec::new();
+        for (k, e) in events.iter().enumerate() {
+            match e.infector.and_then(|n| last_infection.get(&n)) {
+                Some(parent) => children[*parent].push(k),
+                None => roots.push(k),
+            }
+            last_infection.insert(e.infectee, k);
+        }
+        TransmissionTree {
+            events: events.to_vec(),
+            children,
+            roots,
+        }
+    }
+
+    pub fn events(&self) -> &[InfectionEvent] {
+        &self.events
+    }
+
+    // Number of infections directly caused by each infection.
+    pub fn offspring(&self) -> Vec<usize> {
+        self.children.iter().map(|c| c.len()).collect()
+    }
+
+    // One row per infection, with the infector fields empty for the roots.
+    pub fn write_csv<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let opt = |x: Option<usize>| x.map(|x| x.to_string()).unwrap_or_default();
+        writeln!(
+            out,
+            "day,infectee,infector,network,infector_detected,infector_t"
+        )?;
+        for e in self.events.iter() {
+            writeln!(
+                out,
+                "{},{},{},{},{},{}",
+                e.day,
+                e.infectee,
+                opt(e.infector),
+                e.network.as_deref().unwrap_or(""),
+                e.infector_detected,
+                opt(e.infector_t)
+            )?;
+        }
+        Ok(())
+    }
+
+    // One tree per line, labelled by agent, with the days between an
+    // infection and the one of its infector as branch lengths.
+    pub fn to_newick(&self) -> String {
+        let mut res = String::new();
+        for root in self.roots.iter() {
+            self.write_newick(*root, &mut res);
+            res.push_str(";\n");
+        }
+        res
+    }
+
+    // With an explicit stack, as the chains of infections can be too long
+    // for the call stack, in particular on wasm.
+    fn write_newick(&self, root: usize, out: &mut String) {
+        enum Step {
+            Enter(usize),
+            // The length of the branch to the child, after the child.
+            Length(usize, usize),
+            Comma,
+            Exit(usize),
+        }
+        let mut stack = vec![Step::Enter(root)];
+        while let Some(step) = stack.pop() {
+            match step {
+                Step::Enter(k) => {
+                    let children = &self.children[k];
+                    if children.is_empty() {
+                        write!(out, "{}", self.events[k].infectee).unwrap();
+                        continue;
+                    }
+                    out.push('(');
+                    stack.push(Step::Exit(k));
+                    for (j, child) in children.iter().enumerate().rev() {
+                        stack.push(Step::Length(*child, k));
+                        stack.push(Step::Enter(*child));
+                        if j > 0 {
+                            stack.push(Step::Comma);
+                        }
+                    }
+                }
+                Step::Length(child, k) => {
+                    let length = self.events[child].day - self.events[k].day;
+                    write!(out, ":{}", length).unwrap();
+                }
+                Step::Comma => out.push(','),
+                Step::Exit(k) => write!(out, "){}", self.events[k].infectee).unwrap(),
+            }
+        }
+    }
+
+    // A list with the roots, where each infection has the list of the
+    // infections it caused.
+    pub fn to_json(&self) -> Value {
+        // An infection comes after the one of its infector, so going
+        // backwards the children are done before their parent.
+        let mut nodes: Vec<Option<Value>> = vec![None; self.events.len()];
+        for k in (0..self.events.len()).rev() {
+            let e = &self.events[k];
+            let children: Vec<Value> = self.children[k]
+                .iter()
+                .map(|c| nodes[*c].take().unwrap())
+                .collect();
+            nodes[k] = Some(json!({
+                "day": e.day,
+                "agent": e.infectee,
+                "network": e.network,
+                "infector_detected": e.infector_detected,
+                "infector_t": e.infector_t,
+                "children": children,
+            }));
+        }
+        Value::Array(
+            self.roots
+                .iter()
+                .map(|k| nodes[*k].take().unwrap())
+                .collect(),
+        )
+    }
+}
+
+impl Simulation {
+    // Start recording every new infection. The agents with an active
+    // infection at this point are recorded as infected today, with no
+    // infector.
+    pub fn record_infections(&mut self) {
+        if self.infection_log.is_some() {
+            return;
+        }
+        let log = (0..self.states.len())
+            .filter(|i| {
+                matches!(
+                    self.states[*i],
+                    State::Exposed(_)
+                        | State::Infected(_)
+                        | State::Asymptomatic(_)
+                        | State::Detected(_)
+                )
+            })
+            .map(|i| InfectionEvent::root(self.time, i))
+            .collect();
+        self.infection_log = Some(log);
+    }
+
+    // Empty unless `record_infections` has been called.
+    pub fn infection_log(&self) -> &[InfectionEvent] {
+        self.infection_log.as_deref().unwrap_or(&[])
+    }
+
+    pub fn transmission_tree(&self) -> TransmissionTree {
+        TransmissionTree::new(self.infection_log())
+    }
+
+    pub(crate) fn log_infections(&mut self, infections: &[Infection]) {
+        let day = self.time + 1;
+        let layers = &self.layers;
+        if let Some(log) = &mut self.infection_log {
+            log.extend(infections.iter().map(|infection| {
+                let (detected, t) = match infection.infector_state {
+                    State::Detected(t) => (true, t),
+                    State::Infected(t) | State::Asymptomatic(t) => (false, t),
+                    _ => unreachable!(),
+                };
+                InfectionEvent {
+                    day,
+                    infectee: infection.agent,
+                    infector: Some(infection.infector),
+                    network: Some(layers[infection.layer].spec.name.clone()),
+                    infector_detected: detected,
+                    infector_t: Some(t),
+                }
+            }));
+        }
+    }
+
+    pub(crate) fn log_seed(&mut self, i: usize) {
+        let day = self.time + 1;
+        if let Some(log) = &mut self.infection_log {
+            log.push(InfectionEvent::root(day, i));
+        }
+    }
+}
@@ -0,0 +1,151 @@
+use std::collections::VecDeque;
+
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+
+use crate::day::NewStates;
+use crate::{Simulation, State};
+
+// Order in which a vaccination campaign offers the first doses.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Priority {
+    Random,
+    // From the last age band to the first.
+    OldestFirst,
+    // Agents with more connections in all the networks first.
+    MostContacts,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct Campaign {
+    daily_doses: usize,
+    priority: Priority,
+    order: Vec<usize>,
+    next: usize,
+    // A campaign that was stopped gives no more first doses, but keeps its
+    // daily doses for the second doses still due.
+    stopped: bool,
+}
+
+#[derive(Default, Serialize, Deserialize, Clone)]
+pub(crate) struct Vaccination {
+    campaigns: Vec<Campaign>,
+    // Agents waiting for the second dose, with the day it is due.
+    second_doses: VecDeque<(usize, usize)>,
+}
+
+impl Simulation {
+    fn vaccination_order(&mut self, priority: Priority) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..self.states.len()).collect();
+        // Shuffle first so that ties are broken at random.
+        order.shuffle(&mut self.rng);
+        match priority {
+            Priority::Random => {}
+            Priority::OldestFirst => {
+                let ages = &self.ages;
+                order.sort_by_key(|i| std::cmp::Reverse(ages[*i]));
+            }
+            Priority::MostContacts => {
+                let degree = |i: usize| -> usize {
+                    self.layers
+                        .iter()
+                        .map(|l| l.graph.iternodes(i).count())
+                        .sum()
+                };
+                order.sort_by_cached_key(|i| std::cmp::Reverse(degree(*i)));
+            }
+        }
+        order
+    }
+
+    pub fn start_vaccination(&mut self, daily_doses: usize, priority: Priority) {
+        let order = self.vaccination_order(priority);
+        self.vaccination.campaigns.push(Campaign {
+            daily_doses,
+            priority,
+            order,
+            next: 0,
+            stopped: false,
+        });
+    }
+
+    pub fn undo_start_vaccination(&mut self, daily_doses: usize, priority: Priority) -> bool {
+        let campaigns = &mut self.vaccination.campaigns;
+        if let Some(c) = campaigns
+            .iter_mut()
+            .find(|c| !c.stopped && c.daily_doses == daily_doses && c.priority == priority)
+        {
+            c.stopped = true;
+            true
+        } else {
+            false
+        }
+    }
+
+    // Give today's doses. Second doses go first. Then each campaign walks its
+    // order giving first doses to the Susceptible agents; the agents that are
+    // not Susceptible when their turn comes are skipped. The stopped campaigns
+    // are dropped once there are no second doses left to give.
+    pub(crate) fn vaccinate(&mut self, newstates: &mut NewStates) {
+        if self.vaccination.second_doses.is_empty() {
+            self.vaccination.campaigns.retain(|c| !c.stopped);
+        }
+        let mut doses: usize = self
+            .vaccination
+            .campaigns
+            .iter()
+            .map(|c| c.daily_doses)
+            .sum();
+        while doses > 0 {
+            match self.vaccination.second_doses.front() {
+                Some((day, _)) if *day <= self.time => {}
+                _ => break,
+            }
+            let (_, i) = self.vaccination.second_doses.pop_front().unwrap();
+            let s = newstates.get(&self.states, i);
+            if let State::PartiallyVaccinated(_) = s {
+                self.set_state(newstates, i, State::Vaccinated(0));
+                doses -= 1;
+            }
+        }
+
+        let two_doses = self.config.vaccine_doses > 1;
+        let due = self.time + self.config.vaccine_dose_interval;
+        for k in 0..self.vaccination.campaigns.len() {
+            let c = &self.vaccination.campaigns[k];
+            if c.stopped {
+                continue;
+            }
+            let mut campaign_doses = usize::min(c.daily_doses, doses);
+            while campaign_doses > 0 {
+                let c = &mut self.vaccination.campaigns[k];
+                if c.next == c.order.len() {
+                    break;
+                }
+                let i = c.order[c.next];
+                c.next += 1;
+                if let State::Susceptible = newstates.get(&self.states, i) {
+                    let news = if two_doses {
+                        self.vaccination.second_doses.push_back((due, i));
+                        State::PartiallyVaccinated(0)
+                    } else {
+                        State::Vaccinated(0)
+                    };
+                    self.set_state(newstates, i, news);
+                    campaign_doses -= 1;
+                    doses -= 1;
+                }
+            }
+        }
+    }
+
+    // Fraction of the vaccine efficacy an agent in state `s` has.
+    pub(crate) fn vaccine_protection(&self, s: State) -> f64 {
+        match s {
+            State::Vaccinated(_) => 1.,
+            State::PartiallyVaccinated(_) => self.config.vaccine_first_dose_protection,
+            _ => 0.,
+        }
+    }
+}
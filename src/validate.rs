@@ -0,0 +1,295 @@
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::Config;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reason {
+    Empty,
+    LengthMismatch { other: &'static str },
+    NotAProbability { value: f64 },
+    Negative { value: f64 },
+    NotPositive { value: f64 },
+    NotFinite,
+    ZeroSum,
+    NoAdults,
+    NotInRange { value: f64, min: f64, max: f64 },
+    DuplicateName { name: String },
+    UnknownAgeBand { name: String },
+    UnknownLayer { name: String },
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Reason::Empty => write!(f, "must not be empty"),
+            Reason::LengthMismatch { other } => {
+                write!(f, "must have the same number of elements as {}", other)
+            }
+            Reason::NotAProbability { value } => {
+                write!(f, "must be between 0 and 1, got {}", value)
+            }
+            Reason::Negative { value } => write!(f, "must not be negative, got {}", value),
+            Reason::NotPositive { value } => write!(f, "must be positive, got {}", value),
+            Reason::NotFinite => write!(f, "must be a finite number"),
+            Reason::ZeroSum => write!(f, "must not all be zero"),
+            Reason::NotInRange { value, min, max } => {
+                write!(f, "must be between {} and {}, got {}", min, max, value)
+            }
+            Reason::NoAdults => write!(f, "must contain an adult band with positive weight"),
+            Reason::DuplicateName { name } => write!(f, "{} is used more than once", name),
+            Reason::UnknownAgeBand { name } => write!(f, "there is no age band called {}", name),
+            Reason::UnknownLayer { name } => write!(f, "there is no layer called {}", name),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigError {
+    pub field: String,
+    pub reason: Reason,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.field, self.reason)
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+// What gets sent to the web interface.
+#[derive(Serialize)]
+pub(crate) struct ErrorMessage {
+    pub field: Option<String>,
+    pub message: String,
+}
+
+impl From<&ConfigError> for ErrorMessage {
+    fn from(e: &ConfigError) -> Self {
+        ErrorMessage {
+            field: Some(e.field.clone()),
+            message: e.to_string(),
+        }
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct Validator {
+    errors: Vec<ConfigError>,
+}
+
+impl Validator {
+    pub(crate) fn push(&mut self, field: &str, reason: Reason) {
+        self.errors.push(ConfigError {
+            field: field.to_string(),
+            reason,
+        });
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.errors.len()
+    }
+
+    pub(crate) fn finite(&mut self, field: &str, value: f64) -> bool {
+        if !value.is_finite() {
+            self.push(field, Reason::NotFinite);
+            return false;
+        }
+        true
+    }
+
+    pub(crate) fn probability(&mut self, field: &str, value: f64) {
+        if self.finite(field, value) && !(0. ..=1.).contains(&value) {
+            self.push(field, Reason::NotAProbability { value });
+        }
+    }
+
+    pub(crate) fn non_negative(&mut self, field: &str, value: f64) {
+        if self.finite(field, value) && value < 0. {
+            self.push(field, Reason::Negative { value });
+        }
+    }
+
+    pub(crate) fn positive(&mut self, field: &str, value: f64) {
+        if self.finite(field, value) && value <= 0. {
+            self.push(field, Reason::NotPositive { value });
+        }
+    }
+
+    // A profile gives a daily probability and its last value is used
+    // indefinitely, so it needs at least one element.
+    pub(crate) fn profile(&mut self, field: &str, profile: &[f64]) {
+        if profile.is_empty() {
+            self.push(field, Reason::Empty);
+        }
+        for p in profile {
+            self.probability(field, *p);
+        }
+    }
+
+    pub(crate) fn weights(&mut self, field: &str, weights: &[f64]) {
+        if weights.is_empty() {
+            self.push(field, Reason::Empty);
+            return;
+        }
+        let nerrors = self.errors.len();
+        for w in weights {
+            self.non_negative(field, *w);
+        }
+        if nerrors == self.errors.len() && weights.iter().sum::<f64>() <= 0. {
+            self.push(field, Reason::ZeroSum);
+        }
+    }
+
+    pub(crate) fn finish(self) -> Vec<ConfigError> {
+        self.errors
+    }
+}
+
+impl Config {
+    // Return all the problems with the configuration that would make the
+    // simulation fail or behave nonsensically. An empty list means the
+    // configuration is valid.
+    pub fn validate(&self) -> Vec<ConfigError> {
+        let mut v = Validator::default();
+        for (field, profile) in [
+            (
+                "susceptible_infected_profile",
+                &self.susceptible_infected_profile,
+            ),
+            ("infected_detected_profile", &self.infected_detected_profile),
+            (
+                "asymptomatic_detected_profile",
+                &self.asymptomatic_detected_profile,
+            ),
+            ("infected_severe_profile", &self.infected_severe_profile),
+            ("infected_immune_profile", &self.infected_immune_profile),
+            ("severe_immune_profile", &self.severe_immune_profile),
+            ("severe_dead_profile", &self.severe_dead_profile),
+            (
+                "immune_susceptible_profile",
+                &self.immune_susceptible_profile,
+            ),
+            (
+                "vaccinated_susceptible_profile",
+                &self.vaccinated_susceptible_profile,
+            ),
+        ]
+        .iter()
+        {
+            v.profile(field, profile);
+        }
+        // An empty latency profile disables the Exposed state.
+        for p in self.exposed_infected_profile.iter() {
+            v.probability("exposed_infected_profile", *p);
+        }
+
+        v.probability("asymptomatic_fraction", self.asymptomatic_fraction);
+        v.non_negative(
+            "asymptomatic_infectiousness",
+            self.asymptomatic_infectiousness,
+        );
+
+        if self.total_population == 0 {
+            v.push("total_population", Reason::NotPositive { value: 0. });
+        }
+
+        if self.family_sizes.is_empty() {
+            v.push("family_sizes", Reason::Empty);
+        }
+        if self.family_sizes.contains(&0) {
+            v.push("family_sizes", Reason::NotPositive { value: 0. });
+        }
+        v.weights("family_size_weights", &self.family_size_weights);
+        if !self.family_sizes.is_empty()
+            && !self.family_size_weights.is_empty()
+            && self.family_sizes.len() != self.family_size_weights.len()
+        {
+            v.push(
+                "family_size_weights",
+                Reason::LengthMismatch {
+                    other: "family_sizes",
+                },
+            );
+        }
+
+        self.validate_ages(&mut v);
+        self.validate_strains(&mut v);
+        self.validate_layers(&mut v);
+
+        v.probability("vaccine_efficacy_infection", self.vaccine_efficacy_infection);
+        v.probability("vaccine_efficacy_severe", self.vaccine_efficacy_severe);
+        v.probability(
+            "vaccine_first_dose_protection",
+            self.vaccine_first_dose_protection,
+        );
+        if !(1..=2).contains(&self.vaccine_doses) {
+            v.push(
+                "vaccine_doses",
+                Reason::NotInRange {
+                    value: self.vaccine_doses as f64,
+                    min: 1.,
+                    max: 2.,
+                },
+            );
+        }
+
+        v.positive("average_workplace_size", self.average_workplace_size);
+        v.probability("workplace_connectivity", self.workplace_connectivity);
+        for (field, value) in [
+            ("classroom_size", self.classroom_size),
+            ("classrooms_per_school", self.classrooms_per_school),
+        ]
+        .iter()
+        {
+            if *value == 0 {
+                v.push(field, Reason::NotPositive { value: 0. });
+            }
+        }
+        v.probability("classroom_connectivity", self.classroom_connectivity);
+        v.probability("school_connectivity", self.school_connectivity);
+        v.non_negative("average_world_connections", self.average_world_connections);
+
+        for (field, value) in [
+            (
+                "family_contact_undetected_coef",
+                self.family_contact_undetected_coef,
+            ),
+            (
+                "family_contact_detected_coef",
+                self.family_contact_detected_coef,
+            ),
+            (
+                "workplace_contact_undetected_coef",
+                self.workplace_contact_undetected_coef,
+            ),
+            (
+                "workplace_contact_detected_coef",
+                self.workplace_contact_detected_coef,
+            ),
+            (
+                "school_contact_undetected_coef",
+                self.school_contact_undetected_coef,
+            ),
+            (
+                "school_contact_detected_coef",
+                self.school_contact_detected_coef,
+            ),
+            (
+                "world_contact_undetected_coef",
+                self.world_contact_undetected_coef,
+            ),
+            (
+                "world_contact_detected_coef",
+                self.world_contact_detected_coef,
+            ),
+        ]
+        .iter()
+        {
+            v.non_negative(field, *value);
+        }
+        v.finish()
+    }
+}
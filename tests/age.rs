@@ -0,0 +1,112 @@
+mod common;
+
+use common::index;
+use graph::{Config, Counter, Policy, Simulation};
+use serde_json::{json, Value};
+
+fn config(patch: Value) -> Config {
+    common::config(3, patch)
+}
+
+#[test]
+fn age_counters_add_up() {
+    let mut simulation = Simulation::try_new(config(json!({}))).unwrap();
+    for _ in 0..60 {
+        simulation.tick();
+        let mut total = simulation.counter_by_age()[0].counter.clone();
+        for c in simulation.counter_by_age().iter().skip(1) {
+            for i in 0..Counter::NAMES.len() {
+                total.abs_counter[i] += c.counter.abs_counter[i];
+                total.day_counter[i] += c.counter.day_counter[i];
+            }
+            for i in 0..total.by_network.day_counter.len() {
+                total.by_network.day_counter[i] += c.counter.by_network.day_counter[i];
+                total.by_network.total_counter[i] += c.counter.by_network.total_counter[i];
+            }
+        }
+        assert_eq!(&total, simulation.counter());
+    }
+}
+
+#[test]
+fn age_dependent_susceptibility_and_severity() {
+    let mut simulation = Simulation::try_new(config(json!({
+        "initial_outbreak_size": 50,
+        "susceptibility_by_age": [0., 1., 1., 1., 1.],
+        "infected_severe_profile_by_age": [[0.], [0.], [0.], [0.], [0.5]],
+    })))
+    .unwrap();
+    for _ in 0..120 {
+        simulation.tick();
+        let by_age = simulation.counter_by_age();
+        // Minors in the initial outbreak can still lose their immunity.
+        let minors = &by_age[0].counter;
+        assert_eq!(minors.day_counter[index("infected")], 0);
+        for c in by_age[..4].iter() {
+            assert_eq!(c.counter.day_counter[index("severe")], 0);
+            assert_eq!(c.counter.day_counter[index("unattended")], 0);
+        }
+    }
+    assert!(simulation.counter_by_age()[4].counter.abs_counter[index("dead")] > 0);
+}
+
+#[test]
+fn per_age_values_need_one_entry_per_band() {
+    let errors = config(json!({"susceptibility_by_age": [1., 1.]})).validate();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].field, "susceptibility_by_age");
+    let errors = config(json!({
+        "age_bands": [{"name": "children", "weight": 1.0, "adult": false}]
+    }))
+    .validate();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].field, "age_bands");
+}
+
+// Agents that are infected and traced, or that lose their immunity and are
+// vaccinated, on the same day are new in both states everywhere.
+#[test]
+fn daily_counters_add_up_with_tracing_and_vaccination() {
+    let mut simulation = Simulation::try_new(config(json!({
+        "immune_susceptible_profile": [0., 0.05],
+        "vaccine_doses": 2,
+        "strains": [{"name": "variant", "seed_day": 20, "seed_size": 50}],
+    })))
+    .unwrap();
+    let policies: Vec<Policy> = serde_json::from_value(json!([
+        {"policy": "contact-tracing", "data": {"max_daily_tests": 500},
+         "trigger": {"trigger-variable": "time", "trigger-operator": ">=", "trigger-value": 0},
+         "shutdown": {"trigger-variable": "permanent", "trigger-operator": null,
+                      "trigger-value": 0, "recurrent": false}},
+        {"policy": "vaccination", "data": {"daily_doses": 50, "priority": "random"},
+         "trigger": {"trigger-variable": "time", "trigger-operator": ">=", "trigger-value": 30},
+         "shutdown": {"trigger-variable": "permanent", "trigger-operator": null,
+                      "trigger-value": 0, "recurrent": false}},
+    ]))
+    .unwrap();
+    for p in policies {
+        simulation.add_policy(p).unwrap();
+    }
+    for _ in 0..90 {
+        simulation.tick();
+        let day = &simulation.counter().day_counter;
+        for (i, name) in Counter::NAMES.iter().enumerate() {
+            let by_age: i32 = simulation
+                .counter_by_age()
+                .iter()
+                .map(|c| c.counter.day_counter[i])
+                .sum();
+            assert_eq!(by_age, day[i], "{}", name);
+        }
+        // Only the agents that have been infected have a strain.
+        for state in ["infected", "asymptomatic", "detected", "immune_detected"].iter() {
+            let i = index(state);
+            let by_strain: i32 = simulation
+                .counter_by_strain()
+                .iter()
+                .map(|c| c.counter.day_counter[i])
+                .sum();
+            assert_eq!(by_strain, day[i], "{}", state);
+        }
+    }
+}
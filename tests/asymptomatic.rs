@@ -0,0 +1,56 @@
+mod common;
+
+use common::index;
+use graph::{Config, Counter, Simulation};
+use serde_json::json;
+
+fn config(asymptomatic_fraction: f64) -> Config {
+    common::config(
+        11,
+        json!({ "asymptomatic_fraction": asymptomatic_fraction }),
+    )
+}
+
+fn run(config: Config, days: usize) -> Vec<Counter> {
+    let mut simulation = Simulation::try_new(config).unwrap();
+    (0..days)
+        .map(|_| {
+            simulation.tick();
+            simulation.counter().clone()
+        })
+        .collect()
+}
+
+#[test]
+fn no_asymptomatic_cases_by_default() {
+    for counter in run(config(0.), 60) {
+        assert_eq!(counter.abs_counter[index("asymptomatic")], 0);
+    }
+}
+
+#[test]
+fn asymptomatic_cases_never_become_severe() {
+    let days = run(config(1.), 120);
+    // Only the initial outbreak is symptomatic.
+    let new_infected: i32 = days.iter().map(|c| c.day_counter[index("infected")]).sum();
+    let new_asymptomatic: i32 = days
+        .iter()
+        .map(|c| c.day_counter[index("asymptomatic")])
+        .sum();
+    assert_eq!(new_infected, 0);
+    assert!(new_asymptomatic > 0);
+    let severe: i32 = days.iter().map(|c| c.day_counter[index("severe")]).sum();
+    let unattended: i32 = days
+        .iter()
+        .map(|c| c.day_counter[index("unattended")])
+        .sum();
+    // At most the 20 agents of the initial outbreak.
+    assert!(severe + unattended <= 20);
+}
+
+#[test]
+fn asymptomatic_fraction_is_a_probability() {
+    let errors = config(1.5).validate();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].field, "asymptomatic_fraction");
+}
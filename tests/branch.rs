@@ -0,0 +1,91 @@
+mod common;
+
+use graph::{Scenario, Simulation};
+use serde_json::json;
+
+fn simulation() -> Simulation {
+    Simulation::try_new(common::config(8, json!({}))).unwrap()
+}
+
+fn scenario(value: serde_json::Value) -> Scenario {
+    serde_json::from_value(value).unwrap()
+}
+
+#[test]
+fn forks_are_independent() {
+    let mut simulation = simulation();
+    for _ in 0..10 {
+        simulation.tick();
+    }
+    let mut same = simulation.fork(None);
+    let mut other = simulation.fork(Some(3));
+    for _ in 0..20 {
+        same.tick();
+        other.tick();
+    }
+    assert_eq!(simulation.get_time(), 10);
+    for _ in 0..20 {
+        simulation.tick();
+    }
+    assert_eq!(same.counter(), simulation.counter());
+    assert_eq!(same.to_bytes(), simulation.to_bytes());
+    assert_ne!(other.counter(), simulation.counter());
+}
+
+#[test]
+fn branches_are_compared_with_the_first() {
+    let mut simulation = simulation();
+    for _ in 0..15 {
+        simulation.tick();
+    }
+    let scenarios = vec![
+        scenario(json!({"name": "nothing"})),
+        scenario(json!({"name": "again"})),
+        scenario(json!({
+            "name": "lockdown",
+            "policies": [{
+                "policy": "lockdown",
+                "data": {"connections_cut_fraction": 0.9},
+                "trigger": {"trigger-variable": "time", "trigger-operator": ">=", "trigger-value": 0},
+                "shutdown": {"trigger-variable": "permanent", "trigger-operator": null,
+                             "trigger-value": 0, "recurrent": false},
+            }],
+        })),
+    ];
+    let report = simulation.run_branches(&scenarios, 100).unwrap();
+    assert_eq!(report.fork_day, 15);
+    assert_eq!(simulation.get_time(), 15);
+    let [base, again, lockdown] = [&report.branches[0], &report.branches[1], &report.branches[2]];
+    assert_eq!(base.days, 100);
+    assert_eq!(again.attack_rate, base.attack_rate);
+    assert_eq!(report.diffs[1].deaths, 0);
+    assert!(lockdown.attack_rate < base.attack_rate);
+    let diff = &report.diffs[2];
+    assert_eq!(diff.name, "lockdown");
+    assert_eq!(diff.deaths, lockdown.deaths - base.deaths);
+    assert_eq!(diff.peak_severe, lockdown.peak_severe - base.peak_severe);
+    assert_eq!(diff.attack_rate, lockdown.attack_rate - base.attack_rate);
+}
+
+#[test]
+fn strain_seeds_count_as_infections_with_a_latent_period() {
+    // Nobody transmits, so the only infections are the seeds.
+    let config = common::config(
+        8,
+        json!({
+            "susceptible_infected_profile": [0.0],
+            "exposed_infected_profile": [0.0, 0.5],
+            "strains": [{
+                "name": "variant",
+                "seed_day": 3,
+                "seed_size": 50,
+                "susceptible_infected_profile": [0.0],
+            }],
+        }),
+    );
+    let simulation = Simulation::try_new(config).unwrap();
+    let report = simulation
+        .run_branches(&[scenario(json!({"name": "seeds"}))], 10)
+        .unwrap();
+    assert_eq!(report.branches[0].attack_rate, 50. / 5000.);
+}
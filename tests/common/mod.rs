@@ -0,0 +1,22 @@
+// Helpers shared by the integration tests. Each test uses only some of them.
+#![allow(dead_code)]
+
+use graph::{Config, Counter};
+use serde_json::Value;
+
+// The default configuration with 5000 agents and the given seed, and the
+// fields of `patch` on top, which may change the population too.
+pub fn config(seed: u64, patch: Value) -> Config {
+    let mut config = serde_json::to_value(Config::default()).unwrap();
+    config["total_population"] = 5000.into();
+    config["seed"] = seed.into();
+    for (k, v) in patch.as_object().unwrap() {
+        config[k] = v.clone();
+    }
+    serde_json::from_value(config).unwrap()
+}
+
+// The position of a state in the counters.
+pub fn index(name: &str) -> usize {
+    Counter::NAMES.iter().position(|n| *n == name).unwrap()
+}
@@ -0,0 +1,59 @@
+mod common;
+
+use graph::{run_ensemble, Config, EnsembleSpec, Reason};
+use serde_json::json;
+
+fn small_config() -> Config {
+    common::config(11, json!({"total_population": 2000}))
+}
+
+#[test]
+fn bands_are_ordered_and_reproducible() {
+    let spec = EnsembleSpec {
+        replicas: 5,
+        days: 30,
+        quantiles: vec![0.05, 0.5, 0.95],
+    };
+    let summary = run_ensemble(&small_config(), &[], &spec).unwrap();
+    assert_eq!(summary.days.len(), 31);
+    for day in summary.days.iter() {
+        for band in day.abs_counter.iter() {
+            assert!(band.quantiles[0] <= band.quantiles[1]);
+            assert!(band.quantiles[1] <= band.quantiles[2]);
+            assert_eq!(band.quantiles[1], band.median);
+        }
+    }
+    let again = run_ensemble(&small_config(), &[], &spec).unwrap();
+    assert_eq!(summary.days[30].abs_counter, again.days[30].abs_counter);
+}
+
+#[test]
+fn quantiles_must_be_probabilities() {
+    let spec = EnsembleSpec {
+        replicas: 1,
+        days: 1,
+        quantiles: vec![0.5, 1.5, -0.1, f64::NAN],
+    };
+    let errors = run_ensemble(&small_config(), &[], &spec).err().unwrap();
+    let reasons: Vec<Reason> = errors.into_iter().map(|e| e.reason).collect();
+    assert_eq!(
+        reasons,
+        vec![
+            Reason::NotAProbability { value: 1.5 },
+            Reason::NotAProbability { value: -0.1 },
+            Reason::NotFinite,
+        ]
+    );
+}
+
+#[test]
+fn at_least_one_replica() {
+    let spec = EnsembleSpec {
+        replicas: 0,
+        days: 1,
+        quantiles: vec![0.5],
+    };
+    let errors = run_ensemble(&small_config(), &[], &spec).err().unwrap();
+    assert_eq!(errors[0].field, "replicas");
+    assert_eq!(errors[0].reason, Reason::NotPositive { value: 0. });
+}
@@ -0,0 +1,55 @@
+mod common;
+
+use common::{config, index};
+use graph::{Counter, Simulation};
+use serde_json::json;
+
+// Mean number of infections in the first 60 days.
+fn mean_infections(event_driven: bool) -> f64 {
+    let seeds = 8;
+    let mut total = 0;
+    for seed in 0..seeds {
+        let mut simulation =
+            Simulation::try_new(config(seed, json!({ "event_driven": event_driven }))).unwrap();
+        for _ in 0..60 {
+            simulation.tick();
+            total += simulation.counter().day_counter[index("infected")];
+        }
+    }
+    total as f64 / seeds as f64
+}
+
+#[test]
+fn same_outcome_as_checking_every_agent() {
+    let everyone = mean_infections(false);
+    let active = mean_infections(true);
+    assert!((everyone - active).abs() < 0.05 * everyone);
+}
+
+#[test]
+fn counters_stay_consistent() {
+    let mut simulation = Simulation::try_new(config(
+        3,
+        json!({
+            "event_driven": true,
+            "asymptomatic_fraction": 0.3,
+            "cross_immunity": [[0.5]],
+            "immune_susceptible_profile": [0., 0.05],
+        }),
+    )).unwrap();
+    let population: i32 = simulation.counter().abs_counter.iter().sum();
+    let mut lost_immunity = 0;
+    for _ in 0..100 {
+        simulation.tick();
+        let counter = simulation.counter();
+        lost_immunity += counter.day_counter[index("susceptible")];
+        assert_eq!(counter.abs_counter.iter().sum::<i32>(), population);
+        assert!(counter.abs_counter.iter().all(|x| *x >= 0));
+        let by_age = simulation.counter_by_age();
+        for i in 0..Counter::NAMES.len() {
+            let total: i32 = by_age.iter().map(|c| c.counter.abs_counter[i]).sum();
+            assert_eq!(total, counter.abs_counter[i]);
+        }
+    }
+    assert!(lost_immunity > 0);
+}
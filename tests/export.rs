@@ -0,0 +1,169 @@
+mod common;
+
+use graph::{ColumnData, Counter, ImportedNetworks, Metric, Policy, Simulation, Table};
+use serde_json::{json, Value};
+
+fn simulation(patch: Value) -> Simulation {
+    Simulation::try_new(common::config(21, patch)).unwrap()
+}
+
+fn run(days: usize) -> (Simulation, Table) {
+    let mut simulation = simulation(json!({"history": Metric::ALL}));
+    let policy: Policy = serde_json::from_value(json!({
+        "policy": "lockdown",
+        "data": {"connections_cut_fraction": 0.5},
+        "trigger": {"trigger-variable": "time", "trigger-operator": "==", "trigger-value": 5},
+        "shutdown": {"trigger-variable": "duration", "trigger-operator": "==", "trigger-value": 10},
+    }))
+    .unwrap();
+    simulation.add_policy(policy).unwrap();
+    for _ in 0..days {
+        simulation.tick();
+    }
+    let table = Table::daily(simulation.history());
+    (simulation, table)
+}
+
+fn column<'a>(table: &'a Table, name: &str) -> &'a ColumnData {
+    &table.columns.iter().find(|c| c.name == name).unwrap().data
+}
+
+#[test]
+fn daily_table_has_every_metric() {
+    let (simulation, table) = run(30);
+    assert_eq!(table.nrows(), 31);
+    let history = simulation.history();
+    for (k, name) in Counter::NAMES.iter().enumerate() {
+        match column(&table, name) {
+            ColumnData::Int(v) => {
+                assert_eq!(v[30], history[30].counter.as_ref().unwrap().abs_counter[k] as i64)
+            }
+            _ => panic!("{} is not an integer column", name),
+        }
+        assert!(matches!(column(&table, &format!("new_{}", name)), ColumnData::Int(_)));
+    }
+    assert!(matches!(column(&table, "daily_r"), ColumnData::Float(_)));
+    match column(&table, "policy_lockdown") {
+        ColumnData::Bool(v) => {
+            let active: Vec<usize> = (0..v.len()).filter(|t| v[*t]).collect();
+            assert_eq!(active, (5..15).collect::<Vec<_>>());
+        }
+        _ => panic!("policy_lockdown is not a boolean column"),
+    }
+}
+
+#[test]
+fn only_recorded_metrics_become_columns() {
+    let mut simulation = simulation(json!({"history": ["daily_r"]}));
+    simulation.tick();
+    let table = Table::daily(simulation.history());
+    let names: Vec<&str> = table.columns.iter().map(|c| &c.name[..]).collect();
+    assert_eq!(names, vec!["time", "daily_r"]);
+}
+
+#[test]
+fn csv_output() {
+    let (simulation, table) = run(10);
+    let mut out = Vec::new();
+    table.write_csv(&mut out).unwrap();
+    let text = String::from_utf8(out).unwrap();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines.len(), 12);
+    assert!(lines[0].starts_with("time,susceptible,"));
+    assert!(lines[0].ends_with(",policy_cut_connections"));
+
+    let mut out = Vec::new();
+    simulation.agent_table().write_csv(&mut out).unwrap();
+    let text = String::from_utf8(out).unwrap();
+    let mut lines = text.lines();
+    assert_eq!(lines.next(), Some("agent,state,age_band,strain,infections_caused"));
+    let population: i32 = simulation.counter().abs_counter.iter().sum();
+    assert_eq!(lines.count(), population as usize);
+}
+
+fn csv(table: &Table) -> String {
+    let mut out = Vec::new();
+    table.write_csv(&mut out).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+fn sorted_lines(text: &str) -> Vec<&str> {
+    let mut lines: Vec<&str> = text.lines().collect();
+    lines.sort_unstable();
+    lines
+}
+
+#[test]
+fn contact_networks_round_trip() {
+    let (simulation, _) = run(10);
+    let nodes = csv(&simulation.node_table());
+    assert!(nodes.starts_with("id,household,workplace,school,age,state\n"));
+
+    let mut networks = ImportedNetworks::from_nodes(&nodes).unwrap();
+    let mut nedges = 0;
+    for layer in simulation.layer_names() {
+        let edges = csv(&simulation.edge_table(&layer).unwrap());
+        nedges += edges.lines().count() - 1;
+        networks.add_layer(&layer, &edges).unwrap();
+    }
+    let config = serde_json::from_value(json!({"seed": 21})).unwrap();
+    let imported = Simulation::from_networks(config, &networks).unwrap();
+    for layer in simulation.layer_names() {
+        let original = csv(&simulation.edge_table(&layer).unwrap());
+        let copy = csv(&imported.edge_table(&layer).unwrap());
+        assert_eq!(sorted_lines(&original), sorted_lines(&copy));
+    }
+    assert!(simulation.edge_table("nowhere").is_none());
+
+    let mut out = Vec::new();
+    simulation.write_graphml(&mut out).unwrap();
+    let graphml = String::from_utf8(out).unwrap();
+    let population: i32 = simulation.counter().abs_counter.iter().sum();
+    assert_eq!(graphml.matches("<node ").count(), population as usize);
+    assert_eq!(graphml.matches("<edge ").count(), nedges);
+    assert!(graphml.contains(r#"<key id="v4" for="node" attr.name="state" attr.type="string"/>"#));
+    assert!(graphml.contains(r#"<data key="v4">"#));
+}
+
+#[cfg(feature = "columnar")]
+#[test]
+fn arrow_and_parquet_round_trip() {
+    use arrow::array::{Array, Int64Array, StringArray};
+    use arrow::ipc::reader::FileReader;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use std::io::Cursor;
+
+    let (simulation, table) = run(10);
+    let batch = table.to_record_batch().unwrap();
+
+    let mut ipc = Vec::new();
+    table.write_ipc(&mut ipc).unwrap();
+    let read: Vec<_> = FileReader::try_new(Cursor::new(ipc), None)
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(read, vec![batch.clone()]);
+
+    let path = std::env::temp_dir().join("graph_export_test.parquet");
+    table
+        .write_parquet(std::fs::File::create(&path).unwrap())
+        .unwrap();
+    let read: Vec<_> = ParquetRecordBatchReaderBuilder::try_new(std::fs::File::open(&path).unwrap())
+        .unwrap()
+        .build()
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(read.len(), 1);
+    assert_eq!(read[0].columns(), batch.columns());
+    std::fs::remove_file(&path).unwrap();
+
+    let agents = simulation.agent_table().to_record_batch().unwrap();
+    let population: i32 = simulation.counter().abs_counter.iter().sum();
+    assert_eq!(agents.num_rows(), population as usize);
+    let ids = agents.column(0).as_any().downcast_ref::<Int64Array>().unwrap();
+    assert_eq!(ids.value(4999), 4999);
+    let strains = agents.column(3).as_any().downcast_ref::<StringArray>().unwrap();
+    assert!(strains.null_count() > 0);
+    assert!(strains.iter().flatten().all(|s| s == "original"));
+}
@@ -0,0 +1,49 @@
+mod common;
+
+use common::index;
+use graph::{Config, Simulation};
+use serde_json::json;
+
+fn config(exposed_infected_profile: &[f64]) -> Config {
+    common::config(
+        7,
+        json!({ "exposed_infected_profile": exposed_infected_profile }),
+    )
+}
+
+#[test]
+fn no_latent_period_by_default() {
+    let mut simulation = Simulation::try_new(config(&[])).unwrap();
+    for _ in 0..60 {
+        simulation.tick();
+        assert_eq!(simulation.counter().abs_counter[index("exposed")], 0);
+    }
+}
+
+#[test]
+fn exposed_agents_become_infected() {
+    // Nobody leaves the latent period during the first three days.
+    let mut simulation = Simulation::try_new(config(&[0., 0., 0., 0.5])).unwrap();
+    let mut exposed = 0;
+    for _ in 0..3 {
+        simulation.tick();
+        assert_eq!(simulation.counter().day_counter[index("infected")], 0);
+        exposed += simulation.counter().day_counter[index("exposed")];
+    }
+    assert_eq!(simulation.counter().abs_counter[index("exposed")], exposed);
+    let mut infected = 0;
+    for _ in 0..60 {
+        simulation.tick();
+        infected += simulation.counter().day_counter[index("infected")];
+    }
+    assert!(exposed > 0);
+    assert!(infected > 0);
+}
+
+#[test]
+fn latency_profile_can_be_empty_but_not_invalid() {
+    assert!(config(&[]).validate().is_empty());
+    let errors = config(&[0.5, 1.5]).validate();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].field, "exposed_infected_profile");
+}
@@ -0,0 +1,49 @@
+mod common;
+
+use graph::Simulation;
+use serde_json::{json, Value};
+
+fn simulation(patch: Value) -> Simulation {
+    Simulation::try_new(common::config(5, patch)).unwrap()
+}
+
+#[test]
+fn history_matches_the_getters() {
+    let mut simulation = simulation(json!({}));
+    let mut expected = vec![simulation.counter().clone()];
+    for _ in 0..30 {
+        simulation.tick();
+        expected.push(simulation.counter().clone());
+        let last = simulation.history().last().unwrap();
+        assert_eq!(last.time, simulation.get_time());
+        let r = simulation.get_daily_r();
+        assert!(last.daily_r.unwrap() == r || r.is_nan());
+        assert!(last.by_age.is_none());
+    }
+    let history = simulation.history();
+    assert_eq!(history.len(), 31);
+    for (day, (entry, counter)) in history.iter().zip(expected.iter()).enumerate() {
+        assert_eq!(entry.time, day);
+        assert_eq!(entry.counter.as_ref(), Some(counter));
+    }
+    let tail: Vec<usize> = simulation.history_from(25).map(|e| e.time).collect();
+    assert_eq!(tail, vec![25, 26, 27, 28, 29, 30]);
+    assert_eq!(simulation.history_from(31).count(), 0);
+}
+
+#[test]
+fn only_the_configured_metrics_are_recorded() {
+    let mut recorded = simulation(json!({"history": ["by_age", "daily_r"]}));
+    recorded.tick();
+    let entry = &recorded.history()[1];
+    assert!(entry.counter.is_none());
+    assert!(entry.daily_serial_interval.is_none());
+    assert!(entry.daily_r.is_some());
+    assert_eq!(entry.by_age.as_ref(), Some(&recorded.counter_by_age()));
+    let value = serde_json::to_value(entry).unwrap();
+    assert!(value.get("abs_counter").is_none());
+
+    let mut disabled = simulation(json!({"history": []}));
+    disabled.tick();
+    assert!(disabled.history().is_empty());
+}
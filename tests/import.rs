@@ -0,0 +1,161 @@
+mod common;
+
+use std::collections::HashSet;
+use std::fmt::Write;
+
+use graph::{Config, ImportError, ImportedNetworks, Simulation};
+use serde_json::json;
+
+const NNODES: usize = 400;
+
+// The population is the number of nodes.
+fn config() -> Config {
+    common::config(
+        5,
+        json!({"initial_outbreak_size": 20, "world_contact_undetected_coef": 0.2}),
+    )
+}
+
+// Households of four with two adults, who work in workplaces of ten.
+fn nodes() -> String {
+    let mut res = "id,household,age,workplace\n".to_string();
+    for i in 0..NNODES {
+        let adult = i % 4 < 2;
+        let age = if adult { "35" } else { "0-19" };
+        let workplace = if adult { format!("w{}", i / 20) } else { String::new() };
+        writeln!(res, "n{},h{},{},{}", i, i / 4, age, workplace).unwrap();
+    }
+    res
+}
+
+// A ring where each node is linked to the next two.
+fn ring(weight: f64) -> (String, HashSet<(usize, usize)>) {
+    let mut text = "source\ttarget\tweight\n".to_string();
+    let mut edges = HashSet::new();
+    for i in 0..NNODES {
+        for j in [i + 1, i + 2].iter().map(|j| j % NNODES) {
+            writeln!(text, "n{}\tn{}\t{}", i, j, weight).unwrap();
+            edges.insert((usize::min(i, j), usize::max(i, j)));
+        }
+    }
+    (text, edges)
+}
+
+#[test]
+fn imported_contacts_are_used() {
+    let mut networks = ImportedNetworks::from_nodes(&nodes()).unwrap();
+    let (world, edges) = ring(1.);
+    networks.add_layer("world", &world).unwrap();
+    let mut simulation = Simulation::from_networks(config(), &networks).unwrap();
+    let population: i32 = simulation.counter().abs_counter.iter().sum();
+    assert_eq!(population as usize, NNODES);
+
+    simulation.record_infections();
+    for _ in 0..40 {
+        simulation.tick();
+    }
+    let mut nworld = 0;
+    for e in simulation.infection_log().iter() {
+        let (i, j) = match e.infector {
+            Some(j) => (e.infectee, j),
+            None => continue,
+        };
+        match e.network.as_deref() {
+            Some("world") => {
+                assert!(edges.contains(&(usize::min(i, j), usize::max(i, j))));
+                nworld += 1;
+            }
+            Some("family") => assert_eq!(i / 4, j / 4),
+            Some("workplace") => assert_eq!(i / 20, j / 20),
+            Some("school") => assert!(i % 4 >= 2 && j % 4 >= 2),
+            _ => unreachable!(),
+        }
+    }
+    assert!(nworld > 0);
+}
+
+#[test]
+fn links_with_no_weight_do_not_transmit() {
+    let mut networks = ImportedNetworks::from_nodes(&nodes()).unwrap();
+    networks.add_layer("world", &ring(0.).0).unwrap();
+    let mut simulation = Simulation::from_networks(config(), &networks).unwrap();
+    simulation.record_infections();
+    for _ in 0..40 {
+        simulation.tick();
+    }
+    assert!(simulation
+        .infection_log()
+        .iter()
+        .all(|e| e.network.as_deref() != Some("world")));
+}
+
+#[test]
+fn edges_must_match_the_nodes() {
+    let mut networks = ImportedNetworks::from_nodes(&nodes()).unwrap();
+    let error = networks
+        .add_layer("world", "source,target\nn1,n2\nn3,n400\n")
+        .unwrap_err();
+    assert_eq!(
+        error,
+        ImportError::InvalidLine {
+            file: "world".to_string(),
+            line: 3,
+            message: "unknown node n400".to_string(),
+        }
+    );
+    let error = networks.add_layer("world", "from,to\nn1,n2\n").unwrap_err();
+    assert_eq!(
+        error,
+        ImportError::MissingColumn {
+            file: "world".to_string(),
+            column: "source".to_string(),
+        }
+    );
+
+    networks.add_layer("metro", "source,target\nn1,n2\n").unwrap();
+    let error = Simulation::from_networks(config(), &networks).err().unwrap();
+    assert_eq!(error, ImportError::UnknownLayer("metro".to_string()));
+
+    let networks = ImportedNetworks::from_nodes("id,household,age\na,1,110\n").unwrap();
+    assert!(Simulation::from_networks(config(), &networks).is_ok());
+    let networks = ImportedNetworks::from_nodes("id,household,age\na,1,old\n").unwrap();
+    let error = Simulation::from_networks(config(), &networks).err().unwrap();
+    assert_eq!(error, ImportError::UnknownAgeBand("old".to_string()));
+}
+
+#[test]
+fn links_are_given_once() {
+    let mut networks = ImportedNetworks::from_nodes(&nodes()).unwrap();
+    for (edges, line) in [
+        ("source,target\nn1,n2\nn1,n2\n", 3),
+        ("source,target\nn1,n2\nn2,n3\nn2,n1\n", 4),
+    ]
+    .iter()
+    {
+        let error = networks.add_layer("world", edges).unwrap_err();
+        assert!(matches!(
+            error,
+            ImportError::InvalidLine { line: l, ref message, .. }
+                if l == *line && message.ends_with("appears more than once")
+        ));
+    }
+}
+
+#[test]
+fn node_columns_must_be_layers() {
+    let nodes = "id,household,age,workplace,state\na,1,35,w,Immune\nb,1,35,w,Dead\n";
+    let networks = ImportedNetworks::from_nodes(nodes).unwrap();
+    let simulation = Simulation::from_networks(config(), &networks).unwrap();
+    assert_eq!(simulation.agent_table().nrows(), 2);
+
+    let nodes = "id,household,age,office\na,1,35,w\n";
+    let networks = ImportedNetworks::from_nodes(nodes).unwrap();
+    let error = Simulation::from_networks(config(), &networks).err().unwrap();
+    assert_eq!(
+        error,
+        ImportError::UnknownColumn {
+            file: "nodes".to_string(),
+            column: "office".to_string(),
+        }
+    );
+}
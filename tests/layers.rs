@@ -0,0 +1,145 @@
+mod common;
+
+use graph::{Config, Policy, Reason, Simulation};
+use serde_json::{json, Value};
+
+fn config(patch: Value) -> Config {
+    common::config(21, patch)
+}
+
+fn transport() -> Value {
+    json!({"contact_layers": [{
+        "name": "transport",
+        "kind": "random",
+        "average_connections": 20.0,
+        "undetected_coef": 0.05,
+        "detected_coef": 0.0,
+        "tracing_priority": 3,
+    }]})
+}
+
+fn layer_infections(simulation: &Simulation, layer: &str) -> i32 {
+    let by_network = &simulation.counter().by_network;
+    let prefix = format!("{}_", layer);
+    by_network
+        .names()
+        .iter()
+        .zip(by_network.total_counter.iter())
+        .filter(|(name, _)| name.starts_with(&prefix))
+        .map(|(_, n)| n)
+        .sum()
+}
+
+#[test]
+fn extra_layers_transmit() {
+    let mut simulation = Simulation::try_new(config(transport())).unwrap();
+    assert_eq!(
+        simulation.layer_names(),
+        vec!["family", "workplace", "school", "world", "transport"]
+    );
+    simulation.record_infections();
+    for _ in 0..30 {
+        simulation.tick();
+    }
+    let n = layer_infections(&simulation, "transport");
+    assert!(n > 0);
+    let logged = simulation
+        .infection_log()
+        .iter()
+        .filter(|e| e.network.as_deref() == Some("transport"))
+        .count();
+    assert_eq!(logged as i32, n);
+}
+
+#[test]
+fn policies_address_layers_by_name() {
+    let policy: Policy = serde_json::from_value(json!({
+        "policy": "close-groups",
+        "data": {"layer": "workplace", "fraction": 1.0},
+        "trigger": {"trigger-variable": "time", "trigger-operator": ">=", "trigger-value": 0},
+        "shutdown": {"trigger-variable": "permanent", "trigger-operator": null,
+                     "trigger-value": 0, "recurrent": false}
+    }))
+    .unwrap();
+    let mut simulation = Simulation::try_new(config(transport())).unwrap();
+    simulation.add_policy(policy).unwrap();
+    simulation.disable_fraction_of_connections("transport", 1.);
+    for _ in 0..30 {
+        simulation.tick();
+    }
+    assert_eq!(layer_infections(&simulation, "workplace"), 0);
+    assert_eq!(layer_infections(&simulation, "transport"), 0);
+    assert!(layer_infections(&simulation, "world") > 0);
+}
+
+#[test]
+fn layer_names_must_be_unique() {
+    let errors = config(json!({"contact_layers": [{
+        "name": "world",
+        "kind": "groups",
+        "average_size": 10.0,
+        "connectivity": 0.5,
+        "undetected_coef": 0.1,
+        "detected_coef": 0.0,
+        "tracing_priority": 1,
+        "age_bands": ["90+"],
+    }]}))
+    .validate();
+    let reasons: Vec<&Reason> = errors.iter().map(|e| &e.reason).collect();
+    assert_eq!(
+        reasons,
+        vec![
+            &Reason::DuplicateName {
+                name: "world".to_string()
+            },
+            &Reason::UnknownAgeBand {
+                name: "90+".to_string()
+            },
+        ]
+    );
+}
+
+#[test]
+fn policies_must_name_existing_layers() {
+    let policy: Policy = serde_json::from_value(json!({
+        "policy": "cut-connections",
+        "data": {"layer": "transport", "fraction": 0.5},
+        "trigger": {"trigger-variable": "time", "trigger-operator": ">=", "trigger-value": 0},
+        "shutdown": {"trigger-variable": "permanent", "trigger-operator": null,
+                     "trigger-value": 0, "recurrent": false}
+    }))
+    .unwrap();
+    let mut simulation = Simulation::try_new(config(json!({}))).unwrap();
+    let errors = simulation.add_policy(policy.clone()).unwrap_err();
+    assert_eq!(errors[0].field, "policies.layer");
+    assert_eq!(
+        errors[0].reason,
+        Reason::UnknownLayer {
+            name: "transport".to_string()
+        }
+    );
+
+    let mut simulation = Simulation::try_new(config(transport())).unwrap();
+    simulation.add_policy(policy).unwrap();
+}
+
+#[test]
+fn tracing_priorities_can_be_any_number() {
+    let mut patch = transport();
+    patch["contact_layers"][0]["tracing_priority"] = usize::MAX.into();
+    let mut simulation = Simulation::try_new(config(patch)).unwrap();
+    let policy: Policy = serde_json::from_value(json!({
+        "policy": "contact-tracing",
+        "data": {"max_daily_tests": 100},
+        "trigger": {"trigger-variable": "time", "trigger-operator": ">=", "trigger-value": 0},
+        "shutdown": {"trigger-variable": "permanent", "trigger-operator": null,
+                     "trigger-value": 0, "recurrent": false}
+    }))
+    .unwrap();
+    simulation.add_policy(policy).unwrap();
+    // The number of test queues used to be the largest priority plus one.
+    for _ in 0..30 {
+        simulation.tick();
+    }
+    assert_eq!(simulation.get_time(), 30);
+}
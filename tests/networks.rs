@@ -0,0 +1,48 @@
+mod common;
+
+use common::index;
+use graph::Simulation;
+use serde_json::json;
+
+fn simulation() -> Simulation {
+    Simulation::try_new(common::config(9, json!({}))).unwrap()
+}
+
+fn network_index(simulation: &Simulation, name: &str) -> usize {
+    let names = simulation.counter().by_network.names();
+    names.iter().position(|n| *n == name).unwrap()
+}
+
+#[test]
+fn network_counts_add_up_to_new_infections() {
+    let mut simulation = simulation();
+    let mut total = 0;
+    for _ in 0..60 {
+        simulation.tick();
+        let counter = simulation.counter();
+        let today: i32 = counter.by_network.day_counter.iter().sum();
+        assert_eq!(today, counter.day_counter[index("infected")]);
+        total += today;
+        assert_eq!(counter.by_network.total_counter.iter().sum::<i32>(), total);
+    }
+    assert!(total > 0);
+}
+
+#[test]
+fn network_counts_match_the_infection_log() {
+    let mut simulation = simulation();
+    simulation.record_infections();
+    for _ in 0..40 {
+        simulation.tick();
+    }
+    let mut expected = vec![0; simulation.counter().by_network.total_counter.len()];
+    for e in simulation.infection_log().iter() {
+        if let Some(network) = &e.network {
+            let detected = if e.infector_detected { "detected" } else { "undetected" };
+            expected[network_index(&simulation, &format!("{}_{}", network, detected))] += 1;
+        }
+    }
+    assert_eq!(simulation.counter().by_network.total_counter, expected);
+    assert!(expected[network_index(&simulation, "family_detected")] > 0);
+    assert!(expected[network_index(&simulation, "world_undetected")] > 0);
+}
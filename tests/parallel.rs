@@ -0,0 +1,43 @@
+#![cfg(feature = "parallel")]
+
+mod common;
+
+use graph::{Counter, Simulation};
+use serde_json::json;
+
+fn trajectory(seed: u64, days: usize, threads: usize) -> Vec<(Counter, f64)> {
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .unwrap();
+    pool.install(|| {
+        let mut simulation = Simulation::try_new(common::config(seed, json!({}))).unwrap();
+        let mut res = Vec::with_capacity(days);
+        for _ in 0..days {
+            simulation.tick();
+            res.push((simulation.counter().clone(), simulation.get_daily_r()));
+        }
+        res
+    })
+}
+
+#[test]
+fn same_seed_and_threads_same_trajectory() {
+    let a = trajectory(42, 60, 4);
+    let b = trajectory(42, 60, 4);
+    // The R is NaN on the days nobody stops being infectious.
+    for ((ca, ra), (cb, rb)) in a.iter().zip(b.iter()) {
+        assert_eq!(ca, cb);
+        assert!(ra == rb || (ra.is_nan() && rb.is_nan()));
+    }
+}
+
+#[test]
+fn counters_add_up_across_threads() {
+    let trajectory = trajectory(7, 60, 3);
+    let population: i32 = trajectory[0].0.abs_counter.iter().sum();
+    for (counter, _) in trajectory.iter() {
+        assert_eq!(counter.abs_counter.iter().sum::<i32>(), population);
+        assert!(counter.abs_counter.iter().all(|x| *x >= 0));
+    }
+}
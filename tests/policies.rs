@@ -0,0 +1,57 @@
+mod common;
+
+use graph::{Policy, PolicyEventKind, Simulation};
+use serde_json::json;
+
+fn small_simulation() -> Simulation {
+    Simulation::try_new(common::config(7, json!({"total_population": 2000}))).unwrap()
+}
+
+// Run until `days` and return the (time, policy, event) triples.
+fn events(simulation: &mut Simulation, days: usize) -> Vec<(usize, &'static str, PolicyEventKind)> {
+    let mut res = Vec::new();
+    while simulation.get_time() < days {
+        simulation.tick();
+        res.extend(
+            simulation
+                .policy_events()
+                .iter()
+                .map(|e| (e.time, e.policy, e.event)),
+        );
+    }
+    res
+}
+
+#[test]
+fn web_interface_format() {
+    // As produced by the policy forms, where disabled fields are null.
+    let policies: Vec<Policy> = serde_json::from_str(
+        r#"[
+        {"policy": "shut-workplaces", "data": {"workplaces": 0.5},
+         "trigger": {"trigger-variable": "time", "trigger-operator": "==", "trigger-value": 3},
+         "shutdown": {"trigger-variable": "permanent", "trigger-operator": null,
+                      "trigger-value": 0, "recurrent": false}},
+        {"policy": "social-distancing", "data": {"workplace-reduction": 0.3, "world-reduction": 0.7},
+         "trigger": {"trigger-variable": "time", "trigger-operator": ">=", "trigger-value": 5},
+         "shutdown": {"trigger-variable": "duration", "trigger-operator": "==",
+                      "trigger-value": 2, "recurrent": true}}
+        ]"#,
+    )
+    .unwrap();
+    let mut simulation = small_simulation();
+    for p in policies {
+        simulation.add_policy(p).unwrap();
+    }
+    use PolicyEventKind::*;
+    assert_eq!(
+        events(&mut simulation, 12),
+        vec![
+            (3, "shut-workplaces", Applied),
+            (5, "social-distancing", Applied),
+            (7, "social-distancing", Reversed),
+            (8, "social-distancing", Applied),
+            (10, "social-distancing", Reversed),
+            (11, "social-distancing", Applied),
+        ]
+    );
+}
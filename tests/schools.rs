@@ -0,0 +1,81 @@
+mod common;
+
+use graph::{ColumnData, Config, Reason, Simulation};
+use serde_json::{json, Value};
+
+fn config(patch: Value) -> Config {
+    common::config(21, patch)
+}
+
+fn school_infections(simulation: &Simulation) -> i32 {
+    let by_network = &simulation.counter().by_network;
+    by_network
+        .names()
+        .iter()
+        .zip(by_network.total_counter.iter())
+        .filter(|(name, _)| name.starts_with("school_"))
+        .map(|(_, n)| n)
+        .sum()
+}
+
+// Whether each agent belongs to a school age band.
+fn pupils(simulation: &Simulation) -> Vec<bool> {
+    let table = simulation.agent_table();
+    let column = table.columns.iter().find(|c| c.name == "age_band").unwrap();
+    match &column.data {
+        ColumnData::Str(bands) => bands
+            .iter()
+            .map(|b| b.as_deref() == Some("0-19"))
+            .collect(),
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn pupils_meet_at_school_and_not_at_work() {
+    let mut simulation = Simulation::try_new(config(json!({}))).unwrap();
+    simulation.record_infections();
+    for _ in 0..60 {
+        simulation.tick();
+    }
+    let pupils = pupils(&simulation);
+    let mut nschool = 0;
+    for e in simulation.infection_log().iter() {
+        match e.network.as_deref() {
+            Some("school") => {
+                assert!(pupils[e.infectee] && pupils[e.infector.unwrap()]);
+                nschool += 1;
+            }
+            Some("workplace") => {
+                assert!(!pupils[e.infectee] && !pupils[e.infector.unwrap()]);
+            }
+            _ => {}
+        }
+    }
+    assert!(nschool > 0);
+    assert_eq!(school_infections(&simulation), nschool);
+}
+
+#[test]
+fn closed_schools_have_no_infections() {
+    let mut simulation = Simulation::try_new(config(json!({}))).unwrap();
+    simulation.close_fraction_of_groups("school", 1.);
+    for _ in 0..20 {
+        simulation.tick();
+    }
+    assert_eq!(school_infections(&simulation), 0);
+
+    assert!(simulation.undo_close_fraction_of_groups("school", 1.));
+    for _ in 0..20 {
+        simulation.tick();
+    }
+    assert!(school_infections(&simulation) > 0);
+}
+
+#[test]
+fn classrooms_must_not_be_empty() {
+    let errors = config(json!({"classroom_size": 0, "school_connectivity": 2.0})).validate();
+    let fields: Vec<&str> = errors.iter().map(|e| e.field.as_str()).collect();
+    assert_eq!(fields, vec!["classroom_size", "school_connectivity"]);
+    assert_eq!(errors[0].reason, Reason::NotPositive { value: 0. });
+}
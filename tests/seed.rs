@@ -0,0 +1,24 @@
+mod common;
+
+use graph::Simulation;
+use serde_json::json;
+
+fn trajectory(seed: u64, days: usize) -> Vec<graph::Counter> {
+    let mut simulation = Simulation::try_new(common::config(seed, json!({}))).unwrap();
+    let mut res = Vec::with_capacity(days);
+    for _ in 0..days {
+        simulation.tick();
+        res.push(simulation.counter().clone());
+    }
+    res
+}
+
+#[test]
+fn same_seed_same_trajectory() {
+    assert_eq!(trajectory(42, 60), trajectory(42, 60));
+}
+
+#[test]
+fn different_seed_different_trajectory() {
+    assert_ne!(trajectory(1, 60), trajectory(2, 60));
+}
@@ -0,0 +1,111 @@
+mod common;
+
+use graph::{Metric, Policy, Simulation, SnapshotError};
+use serde_json::{json, Value};
+
+fn simulation() -> Simulation {
+    let config = common::config(
+        17,
+        json!({
+            "history": Metric::ALL,
+            "strains": [{"name": "delta", "seed_day": 30, "seed_size": 10}],
+            "cross_immunity": [[1.0, 0.5], [1.0, 1.0]],
+        }),
+    );
+    let mut simulation = Simulation::try_new(config).unwrap();
+    let policies: Value = json!([
+        {"policy": "contact-tracing", "data": {"max_daily_tests": 50},
+         "trigger": {"trigger-variable": "time", "trigger-operator": "==", "trigger-value": 5},
+         "shutdown": {"trigger-variable": "permanent", "trigger-operator": null,
+                      "trigger-value": 0, "recurrent": false}},
+        {"policy": "lockdown", "data": {"connections_cut_fraction": 0.5},
+         "trigger": {"trigger-variable": "time", "trigger-operator": ">=", "trigger-value": 10},
+         "shutdown": {"trigger-variable": "duration", "trigger-operator": "==",
+                      "trigger-value": 15, "recurrent": true}},
+        {"policy": "vaccination", "data": {"daily_doses": 30, "priority": "oldest-first"},
+         "trigger": {"trigger-variable": "time", "trigger-operator": "==", "trigger-value": 15},
+         "shutdown": {"trigger-variable": "permanent", "trigger-operator": null,
+                      "trigger-value": 0, "recurrent": false}},
+    ]);
+    for p in serde_json::from_value::<Vec<Policy>>(policies).unwrap() {
+        simulation.add_policy(p).unwrap();
+    }
+    simulation.record_infections();
+    simulation
+}
+
+#[test]
+fn restored_simulation_continues_identically() {
+    let mut original = simulation();
+    for _ in 0..20 {
+        original.tick();
+    }
+    let bytes = original.to_bytes();
+    let mut restored = Simulation::from_bytes(&bytes).unwrap();
+    assert_eq!(restored.to_bytes(), bytes);
+    for _ in 0..40 {
+        original.tick();
+        restored.tick();
+        assert_eq!(restored.counter(), original.counter());
+    }
+    assert_eq!(restored.get_time(), 60);
+    assert_eq!(restored.infection_log(), original.infection_log());
+    assert_eq!(restored.counter_by_strain(), original.counter_by_strain());
+    assert_eq!(restored.to_bytes(), original.to_bytes());
+}
+
+#[test]
+fn invalid_snapshots_are_rejected() {
+    let mut bytes = simulation().to_bytes();
+    bytes[0] += 1;
+    assert!(matches!(
+        Simulation::from_bytes(&bytes),
+        Err(SnapshotError::Version(_))
+    ));
+    bytes[0] -= 1;
+    bytes.truncate(bytes.len() / 2);
+    assert!(matches!(
+        Simulation::from_bytes(&bytes),
+        Err(SnapshotError::Invalid(_))
+    ));
+    assert!(Simulation::from_bytes(&[]).is_err());
+}
+
+// The position right after the first MessagePack string `key` in the
+// snapshot.
+fn after_key(bytes: &[u8], key: &str) -> usize {
+    let mut pattern = vec![0xa0 | key.len() as u8];
+    pattern.extend(key.as_bytes());
+    bytes
+        .windows(pattern.len())
+        .position(|w| w == &pattern[..])
+        .unwrap()
+        + pattern.len()
+}
+
+#[test]
+fn inconsistent_snapshots_are_rejected() {
+    let bytes = simulation().to_bytes();
+
+    // One agent less in `ages`, an array of small integers of one byte each.
+    let mut short = bytes.clone();
+    let k = after_key(&short, "ages");
+    assert_eq!(short[k], 0xdc);
+    let len = u16::from_be_bytes([short[k + 1], short[k + 2]]);
+    short[k + 1..k + 3].copy_from_slice(&(len - 1).to_be_bytes());
+    short.remove(k + 3);
+    assert!(matches!(
+        Simulation::from_bytes(&short),
+        Err(SnapshotError::Invalid(_))
+    ));
+
+    // One more Susceptible agent in the main counter, which comes first, a number above 255.
+    let mut miscounted = bytes;
+    let k = after_key(&miscounted, "abs_counter");
+    assert_eq!(&miscounted[k..k + 2], &[0x9c, 0xcd]);
+    miscounted[k + 3] ^= 1;
+    assert!(matches!(
+        Simulation::from_bytes(&miscounted),
+        Err(SnapshotError::Invalid(_))
+    ));
+}
@@ -0,0 +1,84 @@
+mod common;
+
+use graph::{Config, ImportedNetworks, LayerStats, Simulation};
+use serde_json::{json, Value};
+
+fn config(patch: Value) -> Config {
+    common::config(21, patch)
+}
+
+fn layer<'a>(stats: &'a [LayerStats], name: &str) -> &'a LayerStats {
+    stats.iter().find(|s| s.name == name).unwrap()
+}
+
+#[test]
+fn parameters_give_the_expected_structure() {
+    let simulation = Simulation::try_new(config(json!({}))).unwrap();
+    let stats = simulation.network_stats();
+    let names: Vec<&str> = stats.iter().map(|s| &s.name[..]).collect();
+    assert_eq!(names, simulation.layer_names());
+
+    let world = layer(&stats, "world");
+    assert!((world.mean_degree - 50.).abs() < 1.);
+    assert_eq!(world.largest_component_fraction, 1.);
+    assert!(world.clustering < 0.05);
+
+    // Workplaces of 15 agents on average, with 80% of the pairs linked.
+    let workplace = layer(&stats, "workplace");
+    let sizes = workplace.group_sizes.as_ref().unwrap();
+    let workers: usize = sizes.iter().map(|(size, n)| size * n).sum();
+    assert_eq!(workers, workplace.nodes);
+    let nworkplaces: usize = sizes.values().sum();
+    assert!((workers as f64 / nworkplaces as f64 - 15.).abs() < 1.);
+    assert!((workplace.clustering - 0.8).abs() < 0.05);
+    assert!(workplace.largest_component_fraction < 0.05);
+
+    // Every household is a component.
+    let family = layer(&stats, "family");
+    assert_eq!(family.nodes, simulation.agent_table().nrows());
+    assert_eq!(&family.component_sizes, family.group_sizes.as_ref().unwrap());
+    assert!(family.component_sizes.keys().all(|s| (1..=5).contains(s)));
+    let total: usize = family.degree_distribution.iter().sum();
+    assert_eq!(total, family.nodes);
+}
+
+#[test]
+fn small_graph() {
+    let nodes = "id,household,age\na,1,20-39\nb,2,20-39\nc,3,20-39\nd,4,20-39\n";
+    let mut networks = ImportedNetworks::from_nodes(nodes).unwrap();
+    // A triangle with a pendant node.
+    let edges = "source,target\na,b\nb,c\nc,a\nc,d\n";
+    networks.add_layer("world", edges).unwrap();
+    let simulation = Simulation::from_networks(config(json!({})), &networks).unwrap();
+    let stats = simulation.network_stats();
+    let world = layer(&stats, "world");
+    assert_eq!(world.nodes, 4);
+    assert_eq!(world.edges, 4);
+    assert_eq!(world.degree_distribution, vec![0, 1, 2, 1]);
+    assert_eq!(world.mean_degree, 2.);
+    assert_eq!(world.degree_variance, 0.5);
+    assert!((world.clustering - (1. + 1. + 1. / 3.) / 4.).abs() < 1e-12);
+    assert_eq!(world.component_sizes.iter().collect::<Vec<_>>(), vec![(&4, &1)]);
+    assert_eq!(world.largest_component_fraction, 1.);
+    assert_eq!(world.group_sizes, None);
+
+    let family = layer(&stats, "family");
+    assert_eq!(family.degree_distribution, vec![4]);
+    assert_eq!(family.largest_component_fraction, 0.25);
+}
+
+#[test]
+fn only_links_between_members_count() {
+    // Only a and b have a workplace.
+    let nodes = "id,household,age,workplace\na,1,35,w\nb,2,35,w\nc,3,35,\nd,4,35,\n";
+    let mut networks = ImportedNetworks::from_nodes(nodes).unwrap();
+    networks
+        .add_layer("workplace", "source,target\na,b\nb,c\nc,d\n")
+        .unwrap();
+    let simulation = Simulation::from_networks(config(json!({})), &networks).unwrap();
+    let stats = simulation.network_stats();
+    let workplace = layer(&stats, "workplace");
+    assert_eq!(workplace.nodes, 2);
+    assert_eq!(workplace.edges, 1);
+    assert_eq!(workplace.degree_distribution, vec![0, 2]);
+}
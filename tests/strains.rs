@@ -0,0 +1,97 @@
+mod common;
+
+use common::index;
+use graph::Simulation;
+use serde_json::{json, Value};
+
+fn simulation(patch: Value) -> Simulation {
+    let config = common::config(9, patch);
+    assert!(config.validate().is_empty());
+    Simulation::try_new(config).unwrap()
+}
+
+fn strain() -> Value {
+    json!({
+        "name": "variant",
+        "seed_day": 30,
+        "seed_size": 25,
+        "susceptible_infected_profile": [0.0, 0.03, 0.06, 0.08, 0.08, 0.06, 0.04, 0.02, 0.0],
+    })
+}
+
+#[test]
+fn strain_appears_on_its_seed_day() {
+    let mut simulation = simulation(json!({"strains": [strain()]}));
+    for day in 1..=40 {
+        simulation.tick();
+        let by_strain = simulation.counter_by_strain();
+        assert_eq!(by_strain[1].strain, "variant");
+        let infected: i32 = by_strain[1].counter.abs_counter.iter().sum();
+        if day < 30 {
+            assert_eq!(infected, 0);
+        } else if day == 30 {
+            assert_eq!(by_strain[1].counter.day_counter[index("infected")], 25);
+        } else {
+            assert!(infected >= 25);
+        }
+    }
+}
+
+#[test]
+fn strain_counters_add_up_to_infected_agents() {
+    let mut simulation = simulation(json!({
+        "strains": [strain()],
+        "cross_immunity": [[1.0, 0.0], [1.0, 1.0]],
+    }));
+    for _ in 0..120 {
+        simulation.tick();
+        let by_strain = simulation.counter_by_strain();
+        let counter = simulation.counter();
+        for state in ["infected", "detected", "severe", "dead"].iter() {
+            let i = index(state);
+            let total: i32 = by_strain.iter().map(|c| c.counter.abs_counter[i]).sum();
+            assert_eq!(total, counter.abs_counter[i]);
+        }
+        for c in by_strain.iter() {
+            assert!(c.counter.abs_counter.iter().all(|x| *x >= 0));
+        }
+    }
+}
+
+#[test]
+fn no_cross_immunity_allows_reinfections() {
+    let run = |cross_immunity: Value| {
+        let mut simulation = simulation(json!({
+            "strains": [strain()],
+            "cross_immunity": cross_immunity,
+        }));
+        let mut infections = 0;
+        for _ in 0..200 {
+            simulation.tick();
+            infections += simulation.counter().day_counter[index("infected")];
+        }
+        infections
+    };
+    assert!(run(json!([[1.0, 0.0], [1.0, 1.0]])) > run(json!([])));
+}
+
+#[test]
+fn cross_immunity_must_be_square() {
+    let config = common::config(
+        9,
+        json!({"strains": [strain()], "cross_immunity": [[1.0, 0.5]]}),
+    );
+    let errors = config.validate();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].field, "cross_immunity");
+}
+
+#[test]
+fn strains_are_seeded_after_the_first_day() {
+    let mut strain = strain();
+    strain["seed_day"] = 0.into();
+    strain["seed_size"] = 0.into();
+    let errors = common::config(9, json!({ "strains": [strain] })).validate();
+    let fields: Vec<&str> = errors.iter().map(|e| e.field.as_str()).collect();
+    assert_eq!(fields, vec!["strains.seed_day", "strains.seed_size"]);
+}
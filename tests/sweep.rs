@@ -0,0 +1,57 @@
+mod common;
+
+use graph::{run_sweep, Config, SweepError, SweepSpec};
+use serde_json::json;
+
+fn small_config() -> Config {
+    common::config(5, json!({"total_population": 1000}))
+}
+
+fn spec(json: serde_json::Value) -> SweepSpec {
+    serde_json::from_value(json).unwrap()
+}
+
+#[test]
+fn grid_covers_every_combination() {
+    let spec = spec(serde_json::json!({
+        "parameters": [
+            {"field": "hospital_capacity", "range": {"from": 10, "to": 20, "steps": 3}},
+            {"field": "world_contact_undetected_coef", "values": [0.05, 0.3]},
+        ],
+        "seeds": 2,
+        "days": 5,
+    }));
+    let rows = run_sweep(&small_config(), &[], &spec).unwrap();
+    assert_eq!(rows.len(), 12);
+    assert_eq!(rows[11].variant, 5);
+    // Integer fields stay integers.
+    assert_eq!(rows[4].parameters["hospital_capacity"], 15);
+    assert_eq!(rows[3].parameters["world_contact_undetected_coef"], 0.3);
+}
+
+#[test]
+fn latin_hypercube_visits_every_stratum() {
+    let spec = spec(serde_json::json!({
+        "parameters": [{"field": "workplace_connectivity", "range": {"from": 0.0, "to": 1.0}}],
+        "sampling": {"method": "latin_hypercube", "samples": 4},
+        "days": 1,
+    }));
+    let rows = run_sweep(&small_config(), &[], &spec).unwrap();
+    let mut strata: Vec<usize> = rows
+        .iter()
+        .map(|r| (r.parameters["workplace_connectivity"].as_f64().unwrap() * 4.) as usize)
+        .collect();
+    strata.sort();
+    assert_eq!(strata, vec![0, 1, 2, 3]);
+}
+
+#[test]
+fn unknown_field() {
+    let spec = spec(serde_json::json!({
+        "parameters": [{"field": "hospital_capacityy", "values": [1]}],
+    }));
+    match run_sweep(&small_config(), &[], &spec) {
+        Err(SweepError::UnknownField(f)) => assert_eq!(f, "hospital_capacityy"),
+        _ => panic!("Expected an unknown field error"),
+    }
+}
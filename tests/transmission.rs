@@ -0,0 +1,90 @@
+mod common;
+
+use common::index;
+use graph::{InfectionEvent, Simulation, TransmissionTree};
+use serde_json::{json, Value};
+
+fn simulation(patch: Value) -> Simulation {
+    Simulation::try_new(common::config(11, patch)).unwrap()
+}
+
+#[test]
+fn log_matches_counters() {
+    let mut simulation = simulation(json!({"initial_outbreak_size": 20}));
+    simulation.record_infections();
+    assert_eq!(simulation.infection_log().len(), 20);
+    for _ in 0..60 {
+        simulation.tick();
+        let time = simulation.get_time();
+        let today: Vec<_> = simulation
+            .infection_log()
+            .iter()
+            .filter(|e| e.day == time)
+            .collect();
+        assert_eq!(
+            today.len() as i32,
+            simulation.counter().day_counter[index("infected")]
+        );
+        for e in today {
+            let infector = e.infector.unwrap();
+            assert_ne!(infector, e.infectee);
+            assert!(e.network.is_some());
+        }
+    }
+}
+
+#[test]
+fn tree_exports() {
+    let mut simulation = simulation(json!({"initial_outbreak_size": 5}));
+    simulation.record_infections();
+    for _ in 0..40 {
+        simulation.tick();
+    }
+    let tree = simulation.transmission_tree();
+    let events = tree.events();
+    assert_eq!(
+        tree.offspring().iter().sum::<usize>(),
+        events.len() - 5
+    );
+
+    let newick = tree.to_newick();
+    assert_eq!(newick.lines().count(), 5);
+    assert_eq!(newick.matches('(').count(), newick.matches(')').count());
+
+    let json = tree.to_json();
+    assert_eq!(json.as_array().unwrap().len(), 5);
+
+    let mut csv = Vec::new();
+    tree.write_csv(&mut csv).unwrap();
+    let csv = String::from_utf8(csv).unwrap();
+    assert_eq!(csv.lines().count(), events.len() + 1);
+    let families = events
+        .iter()
+        .filter(|e| e.network.as_deref() == Some("family"))
+        .count();
+    assert_eq!(csv.matches(",family,").count(), families);
+}
+
+#[test]
+fn nothing_recorded_by_default() {
+    let mut simulation = simulation(json!({}));
+    simulation.tick();
+    assert!(simulation.infection_log().is_empty());
+}
+
+#[test]
+fn long_chains_export() {
+    let events: Vec<_> = (0..200_000)
+        .map(|i| InfectionEvent {
+            day: i,
+            infectee: i,
+            infector: i.checked_sub(1),
+            network: Some("family".to_string()),
+            infector_detected: false,
+            infector_t: Some(1),
+        })
+        .collect();
+    let newick = TransmissionTree::new(&events).to_newick();
+    assert_eq!(newick.matches('(').count(), events.len() - 1);
+    assert!(newick.starts_with("((((") && newick.ends_with(")0;\n"));
+}
@@ -0,0 +1,72 @@
+mod common;
+
+use common::index;
+use graph::{Policy, Simulation};
+use serde_json::{json, Value};
+
+fn simulation(mut patch: Value, priority: &str) -> Simulation {
+    patch["vaccine_efficacy_infection"] = 1.0.into();
+    patch["vaccinated_susceptible_profile"] = json!([0.]);
+    let mut simulation = Simulation::try_new(common::config(5, patch)).unwrap();
+    let policy: Policy = serde_json::from_value(json!({
+        "policy": "vaccination",
+        "data": {"daily_doses": 100, "priority": priority},
+        "trigger": {"trigger-variable": "time", "trigger-operator": "==", "trigger-value": 1},
+        "shutdown": {"trigger-variable": "duration", "trigger-operator": "==", "trigger-value": 10}
+    }))
+    .unwrap();
+    simulation.add_policy(policy).unwrap();
+    simulation
+}
+
+#[test]
+fn single_dose_campaign() {
+    let mut simulation = simulation(json!({"vaccine_doses": 1}), "random");
+    for _ in 0..30 {
+        simulation.tick();
+    }
+    // The campaign runs from day 2 to day 11, both included.
+    let counter = simulation.counter();
+    assert_eq!(counter.abs_counter[index("vaccinated")], 1000);
+    assert_eq!(counter.abs_counter[index("partially_vaccinated")], 0);
+}
+
+#[test]
+fn second_doses_take_priority() {
+    let mut simulation = simulation(
+        json!({
+            "vaccine_doses": 2,
+            "vaccine_dose_interval": 3,
+            "vaccine_first_dose_protection": 1.0,
+        }),
+        "random",
+    );
+    let mut new_partial = Vec::new();
+    let mut new_full = Vec::new();
+    for _ in 0..14 {
+        simulation.tick();
+        let c = simulation.counter();
+        new_partial.push(c.day_counter[index("partially_vaccinated")]);
+        new_full.push(c.day_counter[index("vaccinated")]);
+    }
+    // Days 2 to 4 give first doses, which are followed by three days of
+    // second doses, and so on until the campaign stops after day 11. The
+    // second doses still due are given after that.
+    assert_eq!(new_partial, vec![0, 100, 100, 100, 0, 0, 0, 100, 100, 100, 0, 0, 0, 0]);
+    assert_eq!(new_full, vec![0, 0, 0, 0, 100, 100, 100, 0, 0, 0, 100, 100, 100, 0]);
+    assert_eq!(simulation.counter().abs_counter[index("partially_vaccinated")], 0);
+}
+
+#[test]
+fn oldest_first() {
+    let mut simulation = simulation(json!({"vaccine_doses": 1}), "oldest-first");
+    for _ in 0..2 {
+        simulation.tick();
+    }
+    let by_age = simulation.counter_by_age();
+    let vaccinated: Vec<i32> = by_age
+        .iter()
+        .map(|c| c.counter.abs_counter[index("vaccinated")])
+        .collect();
+    assert_eq!(vaccinated, vec![0, 0, 0, 0, 100]);
+}
@@ -0,0 +1,47 @@
+mod common;
+
+use graph::{Config, Reason, Simulation};
+
+fn config(patch: serde_json::Value) -> Config {
+    common::config(0, patch)
+}
+
+#[test]
+fn default_is_valid() {
+    assert!(Config::default().validate().is_empty());
+}
+
+#[test]
+fn reports_every_bad_field() {
+    let errors = config(serde_json::json!({
+        "family_sizes": [1, 2, 3],
+        "family_size_weights": [1.0, 2.0],
+        "workplace_connectivity": 1.5,
+        "severe_dead_profile": [],
+        "infected_detected_profile": [0.1, -0.2],
+    }))
+    .validate();
+    let fields: Vec<&str> = errors.iter().map(|e| e.field.as_str()).collect();
+    assert_eq!(
+        fields,
+        vec![
+            "infected_detected_profile",
+            "severe_dead_profile",
+            "family_size_weights",
+            "workplace_connectivity",
+        ]
+    );
+    assert_eq!(
+        errors[2].reason,
+        Reason::LengthMismatch {
+            other: "family_sizes"
+        }
+    );
+}
+
+#[test]
+fn try_new_does_not_panic() {
+    let bad = config(serde_json::json!({"family_size_weights": [0.0, 0.0, 0.0, 0.0, 0.0]}));
+    let errors = Simulation::try_new(bad).err().unwrap();
+    assert_eq!(errors[0].reason, Reason::ZeroSum);
+}
@@ -3,13 +3,17 @@ import {Simulation, Config} from  "../pkg/index.js";
 
 const NAMES = [
     "Susceptible",
+    "Exposed",
     "Infected (Undetected)",
+    "Asymptomatic",
     "Infected (Detected)",
     "Severe",
     "Unattended",
     "Immune (Undetected)",
     "Immune (Detected)",
     "Dead",
+    "Partially vaccinated",
+    "Vaccinated",
 ];
 
 
@@ -27,8 +31,29 @@ function sleep(ms) {
 
 
 let simulation = null;
-let policies = null;
-let reverse_policies = null;
+
+// Undefined averages (e.g. no recoveries in a day) come as null in the
+// history.
+function or_nan(x){
+	return x == null ? NaN : x;
+}
+
+// Send the days recorded by the simulation from `from_day` onwards.
+function send_history(from_day){
+	for (let day of simulation.get_history(from_day)){
+		postMessage({
+			"type": "COUNTER_DATA",
+			"args": {
+				"time": day.time,
+				"abs_counter_output": make_counter_data(day.abs_counter),
+				"day_counter_output": make_counter_data(day.day_counter),
+				"hospital_capacity": simulation.get_hospital_capacity(),
+				"day_r": or_nan(day.daily_r),
+				"day_serial": or_nan(day.daily_serial_interval),
+			}
+		});
+	}
+}
 
 let timeoutID = null;
 
@@ -41,29 +66,20 @@ let isPaused = true;
 async function init(args){
 	last_received_time = 0;
 	let config = args.config;
-    policies = args.policies;
-    reverse_policies = [];
-	simulation = Simulation.from_js(config);
-	if (simulation===null){
-		throw new Error("Invalid simulation configuation");
+	try{
+		simulation = Simulation.from_js(config);
+	}catch(errors){
+		simulation = null;
+		postMessage({"type": "CONFIG_ERROR", "args": errors});
+		return;
+	}
+	if (!simulation.add_policies(args.policies)){
+		throw new Error("Invalid policies");
 	}
 	postMessage({"type": "STARTED"});
 	isPaused = false;
 	await sleep(0);
-	let raw_counter_data = simulation.get_counter();
-	let abs_counter_output = make_counter_data(raw_counter_data.abs_counter);
-	let day_counter_output = make_counter_data(raw_counter_data.day_counter);
-	postMessage({
-		"type": "COUNTER_DATA",
-		"args": {
-			"time": simulation.get_time(),
-			"abs_counter_output": abs_counter_output,
-			"day_counter_output": day_counter_output,
-			"hospital_capacity": simulation.get_hospital_capacity(),
-            "day_r": simulation.get_daily_r(),
-            "day_serial": simulation.get_daily_serial_interval(),
-		}
-	});
+	send_history(0);
 	//Actually send messages
 	await sleep(0);
 	run();
@@ -82,126 +98,19 @@ function pause(){
 	postMessage({"type": "PAUSED"});
 }
 
-const ops = {
-    ">=": (a,b) => (a >= b),
-    "<=": (a,b) => (a <= b),
-    "==": (a,b) => (a === b),
-}
-
-function runPolicy(policy, data){
-	switch (policy){
-		case "shut-workplaces":
-			simulation.disable_fraction_of_workplaces(data.workplaces);
-			break
-		case "social-distancing":
-			simulation.multiply_undetected_workplace_infectability(1 - data["workplace-reduction"]);
-			simulation.multiply_undetected_world_infectability(1 - data["world-reduction"]);
-			break
-        case "lockdown":
-            simulation.disable_fraction_of_world_connections(data["connections_cut_fraction"])
-            break
-        case "contact-tracing":
-            simulation.set_max_contact_tracing(data["max_daily_tests"]);
-            break;
-        case "enhanced-self-isolation":
-            simulation.multiply_detected_household_infectability(1 - data["household-reduction"]);
-            simulation.multiply_detected_workplace_infectability(1 - data["workplace-reduction"]);
-            simulation.multiply_detected_world_infectability(1 - data["world-reduction"]);
-            break;
-     }
-
-}
-
-function reversePolicy(policy, data){
-	switch (policy){
-		case "shut-workplaces":
-			simulation.undo_disable_fraction_of_workplaces(data.workplaces);
-			break
-		case "social-distancing":
-			simulation.undo_multiply_undetected_workplace_infectability(1 - data["workplace-reduction"]);
-			simulation.undo_multiply_undetected_world_infectability(1 - data["world-reduction"]);
-			break
-        case "lockdown":
-            simulation.undo_disable_fraction_of_world_connections(data["connections_cut_fraction"])
-            break
-        case "contact-tracing":
-            simulation.undo_set_max_contact_tracing(data["max_daily_tests"]);
-            break;
-        case "enhanced-self-isolation":
-            simulation.undo_multiply_detected_household_infectability(1 - data["household-reduction"]);
-            simulation.undo_multiply_detected_workplace_infectability(1 - data["workplace-reduction"]);
-            simulation.undo_multiply_detected_world_infectability(1 - data["world-reduction"]);
-            break;
-     }
-
-}
-
-
-function checkTrigger(obj, trigger){
-	let op = ops[trigger["trigger-operator"]];
-	let v = obj[trigger["trigger-variable"]];
-	let c = trigger["trigger-value"];
-	return op(v, c);
-}
-
 function run(){
 	simulation.tick();
 	let time = simulation.get_time();
-	let raw_counter_data = simulation.get_counter();
-	let abs_counter_output = make_counter_data(raw_counter_data.abs_counter);
-	let day_counter_output = make_counter_data(raw_counter_data.day_counter);
-	postMessage({
-		"type": "COUNTER_DATA",
-		"args": {
-			"time": time,
-			"abs_counter_output": abs_counter_output,
-			"day_counter_output": day_counter_output,
-			"hospital_capacity": simulation.get_hospital_capacity(),
-            "day_r": simulation.get_daily_r(),
-            "day_serial": simulation.get_daily_serial_interval(),
-		}
-	});
+	send_history(time);
 
 
-	let newpolocies = [];
-    let cmpobj = {time: time, ...abs_counter_output};
-	for (let p of policies){
-		if (checkTrigger(cmpobj, p.trigger)){
-			runPolicy(p.policy, p.data);
-			postMessage({
-				"type": "POLICY_APPLIED",
-				"args": {time: time, policy: p.policy, event: "applied",}
-			});
-            if (p.shutdown["trigger-variable"] !== "permanent"){
-                if (p.shutdown["trigger-variable"] === "duration"){
-                    p.shutdown["trigger-variable"] = "time";
-                    p.shutdown["trigger-value"] += time;
-                }
-                reverse_policies.push(p);
-            }
-		}else{
-			newpolocies.push(p);
-		}
+	// The policies are triggered by the simulation itself during the tick.
+	for (let ev of simulation.get_policy_events()){
+		postMessage({
+			"type": ev.event === "applied" ? "POLICY_APPLIED" : "POLICY_REVERSED",
+			"args": ev,
+		});
 	}
-	policies = newpolocies;
-
-    let new_reverse_policies = [];
-    for (let p of reverse_policies){
-        if(checkTrigger(cmpobj, p.shutdown)){
-            reversePolicy(p.policy, p.data);
-            postMessage({
-                "type": "POLICY_REVERSED",
-                "args": {time: time, policy: p.policy, event: "reversed"}
-            });
-            if (p.shutdown.recurrent){
-                policies.push(p);
-            }
-        }else{
-            new_reverse_policies.push(p);
-        }
-    }
-    reverse_policies = new_reverse_policies;
-
 
 	if (time - last_received_time > 20){
 		queue_full = true;